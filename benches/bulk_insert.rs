@@ -0,0 +1,103 @@
+// Вставка ROWS строк тремя способами, нужна база из settings/settings.toml:
+//
+//     cargo bench --bench bulk_insert
+//     cargo bench --bench bulk_insert -- 100000
+//
+// Каждый способ пишет во временную таблицу своего соединения в отдельной транзакции, которая откатывается.
+
+use sqlx::{Connection, PgConnection};
+use sqlx_example::bulk::{bulk_upsert, BulkLoader, CopyRow, RowWriter};
+use sqlx_example::settings;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const RUNS: u32 = 5;
+
+struct NewTodo {
+    name: String,
+    checked: bool,
+}
+
+impl CopyRow for NewTodo {
+    const COLUMNS: &'static [&'static str] = &["name", "checked"];
+    fn write(&self, row: &mut RowWriter<'_>) {
+        row.field(&self.name);
+        row.field(&self.checked);
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    // cargo bench передаёт --bench; cargo test --all-targets запускает без него - базу не трогаем
+    if !std::env::args().any(|arg| arg == "--bench") {
+        return Ok(());
+    }
+    let rows: usize = std::env::args().skip(1).find(|arg| !arg.starts_with("--")).map(|arg| arg.parse()).transpose()?.unwrap_or(10_000);
+    // Arc - замыкания bench возвращают future без заимствований
+    let todos: Arc<Vec<NewTodo>> = Arc::new((0..rows).map(|i| NewTodo { name: format!("bench {}", i), checked: i % 2 == 0 }).collect());
+    let mut conn = PgConnection::connect(&settings::config()?).await?;
+
+    println!("{} rows, best of {} runs", rows, RUNS);
+
+    let time = bench(&mut conn, |tx| {
+        let todos = todos.clone();
+        Box::pin(async move {
+            for todo in todos.iter() {
+                sqlx::query("INSERT INTO bench_todo (name, checked) VALUES ($1, $2)").bind(&todo.name).bind(todo.checked).execute(&mut *tx).await?;
+            }
+            Ok(())
+        })
+    })
+    .await?;
+    report("row by row", rows, time);
+
+    let time = bench(&mut conn, |tx| {
+        let todos = todos.clone();
+        Box::pin(async move {
+            let ids: Vec<i32> = bulk_upsert("bench_todo")
+                .column("name", todos.iter().map(|todo| todo.name.clone()).collect::<Vec<_>>())
+                .column("checked", todos.iter().map(|todo| todo.checked).collect::<Vec<_>>())
+                .on_conflict("DO NOTHING")
+                .fetch_ids(&mut *tx)
+                .await?;
+            assert_eq!(ids.len(), todos.len());
+            Ok(())
+        })
+    })
+    .await?;
+    report("bulk_upsert (UNNEST)", rows, time);
+
+    let time = bench(&mut conn, |tx| {
+        let todos = todos.clone();
+        Box::pin(async move {
+            BulkLoader::<NewTodo>::new("bench_todo").load(&mut *tx, todos.iter()).await?;
+            Ok(())
+        })
+    })
+    .await?;
+    report("COPY binary", rows, time);
+
+    Ok(())
+}
+
+async fn bench<F>(conn: &mut PgConnection, mut f: F) -> Result<Duration, sqlx_example::Error>
+where
+    F: for<'c> FnMut(&'c mut PgConnection) -> futures::future::BoxFuture<'c, Result<(), sqlx_example::Error>>,
+{
+    let mut best = Duration::MAX;
+    for _ in 0..RUNS {
+        let mut tx = conn.begin().await?;
+        sqlx::query("CREATE TEMP TABLE bench_todo (id SERIAL PRIMARY KEY, name VARCHAR(255), checked BOOLEAN DEFAULT false)")
+            .execute(&mut tx)
+            .await?;
+        let start = Instant::now();
+        f(&mut tx).await?;
+        best = best.min(start.elapsed());
+        tx.rollback().await?;
+    }
+    Ok(best)
+}
+
+fn report(name: &str, rows: usize, time: Duration) {
+    println!("{:<22} {:>10.1?} {:>12.0} rows/s", name, time, rows as f64 / time.as_secs_f64());
+}
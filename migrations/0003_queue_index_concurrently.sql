@@ -0,0 +1,3 @@
+-- no-transaction
+-- queue большая, индекс строится без блокировки записи
+CREATE INDEX CONCURRENTLY IF NOT EXISTS index_queue_on_status_scheduled_for ON queue (status, scheduled_for);
@@ -0,0 +1,10 @@
+-- события пишутся в outbox в той же транзакции, что и изменения данных,
+-- relay публикует неотправленные (sent_at IS NULL) и отмечает их
+CREATE TABLE IF NOT EXISTS outbox (
+  id BIGSERIAL PRIMARY KEY,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+  topic TEXT NOT NULL,
+  message JSONB NOT NULL,
+  sent_at TIMESTAMP WITH TIME ZONE
+);
+CREATE INDEX IF NOT EXISTS index_outbox_unsent ON outbox (id) WHERE sent_at IS NULL;
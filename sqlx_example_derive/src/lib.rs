@@ -0,0 +1,239 @@
+// #[derive(SqlEnum)] - сопоставление enum без полей с TEXT или с enum типом Postgres.
+//
+//     #[derive(SqlEnum, Debug, PartialEq)]
+//     #[sql_enum(rename_all = "lowercase", case_insensitive, column = "variant")]
+//     pub enum Variant { One, Two }
+//
+// Атрибуты enum:
+//     type_name = "mood"     - enum тип Postgres (CREATE TYPE mood AS ENUM ...), без него TEXT/VARCHAR
+//     rename_all = "..."     - lowercase, UPPERCASE, snake_case, SCREAMING_SNAKE_CASE, kebab-case, camelCase, PascalCase
+//     case_insensitive       - Decode принимает значение в любом регистре (Encode всегда пишет каноническое)
+//     column = "variant"     - дополнительно FromRow из указанной колонки (для query_as::<_, Variant>)
+// Атрибуты варианта:
+//     rename = "..."         - каноническое значение
+//     alias = "..."          - дополнительное значение для Decode (можно несколько раз)
+//
+// Генерирует: VARIANTS, as_str(), FromStr, sqlx::Type/Encode/Decode для Postgres.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Lit, Meta, NestedMeta};
+
+#[proc_macro_derive(SqlEnum, attributes(sql_enum))]
+pub fn derive_sql_enum(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input).unwrap_or_else(|err| err.to_compile_error()).into()
+}
+
+#[derive(Default)]
+struct EnumAttrs {
+    type_name: Option<String>,
+    rename_all: Option<String>,
+    case_insensitive: bool,
+    column: Option<String>,
+}
+
+#[derive(Default)]
+struct VariantAttrs {
+    rename: Option<String>,
+    aliases: Vec<String>,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let ident = &input.ident;
+    let data = match &input.data {
+        Data::Enum(data) => data,
+        _ => return Err(Error::new_spanned(&input.ident, "SqlEnum can only be derived for enums")),
+    };
+
+    let attrs = enum_attrs(&input.attrs)?;
+
+    let mut variants = Vec::new();
+    let mut names = Vec::new();
+    let mut accepted = Vec::new();
+    for variant in &data.variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(Error::new_spanned(variant, "SqlEnum variants cannot have fields"));
+        }
+        let variant_attrs = variant_attrs(&variant.attrs)?;
+        let name = match variant_attrs.rename {
+            Some(name) => name,
+            None => rename(&variant.ident.to_string(), attrs.rename_all.as_deref(), &variant.ident)?,
+        };
+        let mut values = vec![name.clone()];
+        values.extend(variant_attrs.aliases);
+
+        variants.push(variant.ident.clone());
+        names.push(name);
+        accepted.push(values);
+    }
+
+    let matches = variants.iter().zip(&accepted).map(|(variant, values)| {
+        if attrs.case_insensitive {
+            quote! { _ if [#(#values),*].iter().any(|v| v.eq_ignore_ascii_case(value)) => Ok(#ident::#variant), }
+        } else {
+            quote! { #(#values)|* => Ok(#ident::#variant), }
+        }
+    });
+
+    let ty = match &attrs.type_name {
+        Some(type_name) => quote! {
+            impl ::sqlx::Type<::sqlx::Postgres> for #ident {
+                fn type_info() -> ::sqlx::postgres::PgTypeInfo {
+                    ::sqlx::postgres::PgTypeInfo::with_name(#type_name)
+                }
+            }
+        },
+        None => quote! {
+            impl ::sqlx::Type<::sqlx::Postgres> for #ident {
+                fn type_info() -> ::sqlx::postgres::PgTypeInfo {
+                    <&str as ::sqlx::Type<::sqlx::Postgres>>::type_info()
+                }
+                fn compatible(ty: &::sqlx::postgres::PgTypeInfo) -> bool {
+                    <&str as ::sqlx::Type<::sqlx::Postgres>>::compatible(ty)
+                }
+            }
+        },
+    };
+
+    let from_row = attrs.column.as_ref().map(|column| {
+        quote! {
+            impl<'r> ::sqlx::FromRow<'r, ::sqlx::postgres::PgRow> for #ident {
+                fn from_row(row: &'r ::sqlx::postgres::PgRow) -> ::sqlx::Result<Self> {
+                    ::sqlx::Row::try_get(row, #column)
+                }
+            }
+        }
+    });
+
+    let ident_str = ident.to_string();
+    Ok(quote! {
+        impl #ident {
+            pub const VARIANTS: &'static [#ident] = &[#(#ident::#variants),*];
+
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    #(#ident::#variants => #names,)*
+                }
+            }
+        }
+
+        impl ::std::str::FromStr for #ident {
+            type Err = ::std::string::String;
+
+            fn from_str(value: &str) -> ::std::result::Result<Self, Self::Err> {
+                match value {
+                    #(#matches)*
+                    _ => Err(format!("invalid value {:?} for enum {}", value, #ident_str)),
+                }
+            }
+        }
+
+        #ty
+
+        impl<'q> ::sqlx::Encode<'q, ::sqlx::Postgres> for #ident {
+            fn encode_by_ref(&self, buf: &mut ::sqlx::postgres::PgArgumentBuffer) -> ::sqlx::encode::IsNull {
+                <&str as ::sqlx::Encode<'q, ::sqlx::Postgres>>::encode(self.as_str(), buf)
+            }
+            fn size_hint(&self) -> usize {
+                self.as_str().len()
+            }
+        }
+
+        impl<'r> ::sqlx::Decode<'r, ::sqlx::Postgres> for #ident {
+            fn decode(
+                value: ::sqlx::postgres::PgValueRef<'r>,
+            ) -> ::std::result::Result<Self, ::sqlx::error::BoxDynError> {
+                let value = <&'r str as ::sqlx::Decode<'r, ::sqlx::Postgres>>::decode(value)?;
+                Ok(<#ident as ::std::str::FromStr>::from_str(value)?)
+            }
+        }
+
+        #from_row
+    })
+}
+
+fn enum_attrs(attrs: &[syn::Attribute]) -> syn::Result<EnumAttrs> {
+    let mut out = EnumAttrs::default();
+    for meta in sql_enum_meta(attrs)? {
+        match meta {
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("case_insensitive") => out.case_insensitive = true,
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("type_name") => out.type_name = Some(string(&nv.lit)?),
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("rename_all") => out.rename_all = Some(string(&nv.lit)?),
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("column") => out.column = Some(string(&nv.lit)?),
+            other => return Err(Error::new_spanned(other, "unknown sql_enum attribute")),
+        }
+    }
+    Ok(out)
+}
+
+fn variant_attrs(attrs: &[syn::Attribute]) -> syn::Result<VariantAttrs> {
+    let mut out = VariantAttrs::default();
+    for meta in sql_enum_meta(attrs)? {
+        match meta {
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("rename") => out.rename = Some(string(&nv.lit)?),
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("alias") => out.aliases.push(string(&nv.lit)?),
+            other => return Err(Error::new_spanned(other, "unknown sql_enum variant attribute")),
+        }
+    }
+    Ok(out)
+}
+
+fn sql_enum_meta(attrs: &[syn::Attribute]) -> syn::Result<Vec<NestedMeta>> {
+    let mut out = Vec::new();
+    for attr in attrs.iter().filter(|a| a.path.is_ident("sql_enum")) {
+        match attr.parse_meta()? {
+            Meta::List(list) => out.extend(list.nested),
+            other => return Err(Error::new_spanned(other, "expected #[sql_enum(...)]")),
+        }
+    }
+    Ok(out)
+}
+
+fn string(lit: &Lit) -> syn::Result<String> {
+    match lit {
+        Lit::Str(s) => Ok(s.value()),
+        _ => Err(Error::new_spanned(lit, "expected a string literal")),
+    }
+}
+
+fn rename(name: &str, rule: Option<&str>, span: &syn::Ident) -> syn::Result<String> {
+    let words = split_words(name);
+    let capitalize = |w: &String| {
+        let mut chars = w.chars();
+        chars.next().map(|c| c.to_uppercase().chain(chars).collect::<String>()).unwrap_or_default()
+    };
+    Ok(match rule {
+        None => name.to_string(),
+        Some("lowercase") => name.to_lowercase(),
+        Some("UPPERCASE") => name.to_uppercase(),
+        Some("snake_case") => words.join("_"),
+        Some("SCREAMING_SNAKE_CASE") => words.join("_").to_uppercase(),
+        Some("kebab-case") => words.join("-"),
+        Some("PascalCase") => words.iter().map(capitalize).collect(),
+        Some("camelCase") => {
+            let mut out = words.first().cloned().unwrap_or_default();
+            out.extend(words.iter().skip(1).map(capitalize));
+            out
+        }
+        Some(other) => return Err(Error::new_spanned(span, format!("unknown rename_all rule {:?}", other))),
+    })
+}
+
+// InProgress -> ["in", "progress"]
+fn split_words(name: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    for c in name.chars() {
+        if c == '_' {
+            words.push(String::new());
+        } else if c.is_uppercase() || words.is_empty() {
+            words.push(c.to_lowercase().collect());
+        } else {
+            words.last_mut().unwrap().push(c);
+        }
+    }
+    words.retain(|w| !w.is_empty());
+    words
+}
@@ -0,0 +1,428 @@
+// COPY ... FROM STDIN из Rust структур или CSV файла, bulk_upsert через UNNEST.
+//
+//     struct NewTodo { name: String, checked: bool }
+//     impl CopyRow for NewTodo {
+//         const COLUMNS: &'static [&'static str] = &["name", "checked"];
+//         fn write(&self, row: &mut RowWriter<'_>) {
+//             row.field(&self.name);
+//             row.field(&self.checked);
+//         }
+//     }
+//     let rows = BulkLoader::<NewTodo>::new("todo").load(&mut conn, todos).await?;
+//
+// Binary (по умолчанию) - значения кодируются как sqlx::Encode, поэтому тип поля должен
+// точно совпадать с типом колонки (i32 - INT4, i64 - INT8, ...), иначе COPY упадёт
+// с "incorrect binary data format". CSV - текст с экранированием, Postgres сам приводит типы.
+// Строки копятся в буфер и отправляются кусками по chunk_size байт; send ждёт записи в сокет,
+// поэтому медленная база притормаживает чтение источника, а не раздувает память.
+// Если среди колонок есть SERIAL/IDENTITY (явные id), после COPY их последовательность
+// поднимается до max(id) - см. sequences::resync, отключается resync_sequences(false).
+
+use crate::error::Error;
+use crate::sequences;
+use futures::{Stream, StreamExt};
+use sqlx::encode::IsNull;
+use sqlx::postgres::{PgArgumentBuffer, PgArguments, PgConnection, Postgres};
+use sqlx::{Arguments, Decode, Encode, Executor, Type, TypeInfo};
+use std::borrow::Borrow;
+use std::marker::PhantomData;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Binary,
+    Csv,
+}
+
+// Значение одной колонки
+pub trait CopyValue {
+    // как Encode<Postgres>::encode_by_ref
+    fn encode_binary(&self, buf: &mut PgArgumentBuffer) -> IsNull;
+    // текст для CSV, None - NULL
+    fn to_text(&self) -> Option<String>;
+}
+
+// Строка COPY: колонки и значения в том же порядке. write пишет ровно по полю на колонку,
+// иначе load возвращает Error::Validation и прерывает COPY
+pub trait CopyRow {
+    const COLUMNS: &'static [&'static str];
+    fn write(&self, row: &mut RowWriter<'_>);
+}
+
+pub struct RowWriter<'a> {
+    format: Format,
+    buf: &'a mut PgArgumentBuffer,
+    fields: usize,
+}
+
+impl RowWriter<'_> {
+    pub fn field<V: CopyValue + ?Sized>(&mut self, value: &V) {
+        match self.format {
+            Format::Binary => {
+                // Int32 длина + данные, -1 - NULL
+                let offset = self.buf.len();
+                self.buf.extend_from_slice(&0_i32.to_be_bytes());
+                let len = match value.encode_binary(self.buf) {
+                    IsNull::Yes => -1,
+                    IsNull::No => (self.buf.len() - offset - 4) as i32,
+                };
+                self.buf[offset..offset + 4].copy_from_slice(&len.to_be_bytes());
+            }
+            Format::Csv => {
+                if self.fields > 0 {
+                    self.buf.push(b',');
+                }
+                if let Some(text) = value.to_text() {
+                    write_csv_field(self.buf, &text);
+                }
+            }
+        }
+        self.fields += 1;
+    }
+}
+
+// В CSV без кавычек пустое значение - NULL, поэтому пустая строка всегда в кавычках
+fn write_csv_field(buf: &mut Vec<u8>, text: &str) {
+    if !text.is_empty() && !text.contains([',', '"', '\n', '\r', '\\']) && text.trim() == text {
+        buf.extend_from_slice(text.as_bytes());
+        return;
+    }
+    buf.push(b'"');
+    for b in text.bytes() {
+        if b == b'"' {
+            buf.push(b'"');
+        }
+        buf.push(b);
+    }
+    buf.push(b'"');
+}
+
+// https://www.postgresql.org/docs/current/sql-copy.html#id-1.9.3.55.9.4
+const BINARY_HEADER: &[u8] = b"PGCOPY\n\xff\r\n\0\0\0\0\0\0\0\0\0";
+const BINARY_TRAILER: &[u8] = &(-1_i16).to_be_bytes();
+
+pub struct BulkLoader<T> {
+    table: String,
+    format: Format,
+    chunk_size: usize,
+    resync_sequences: bool,
+    row: PhantomData<fn(&T)>,
+}
+
+impl<T: CopyRow> BulkLoader<T> {
+    pub fn new(table: impl Into<String>) -> Self {
+        BulkLoader { table: table.into(), format: Format::Binary, chunk_size: 64 * 1024, resync_sequences: true, row: PhantomData }
+    }
+
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    // сколько байт копить перед отправкой
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    pub fn resync_sequences(mut self, resync_sequences: bool) -> Self {
+        self.resync_sequences = resync_sequences;
+        self
+    }
+
+    pub fn statement(&self, format: Format, header: bool) -> String {
+        let format = match format {
+            Format::Binary => "binary",
+            Format::Csv => "csv",
+        };
+        let header = if header { ", HEADER true" } else { "" };
+        format!(
+            "COPY {} ({}) FROM STDIN WITH (FORMAT {}{})",
+            quote_ident(&self.table),
+            T::COLUMNS.iter().map(|column| quote_ident(column)).collect::<Vec<_>>().join(", "),
+            format,
+            header
+        )
+    }
+
+    // вернуть число загруженных строк
+    pub async fn load<I>(&self, conn: &mut PgConnection, rows: I) -> Result<u64, Error>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<T>,
+    {
+        self.load_stream(conn, futures::stream::iter(rows)).await
+    }
+
+    pub async fn load_stream<S>(&self, conn: &mut PgConnection, rows: S) -> Result<u64, Error>
+    where
+        S: Stream,
+        S::Item: Borrow<T>,
+    {
+        futures::pin_mut!(rows);
+        let mut copy = conn.copy_in_raw(&self.statement(self.format, false)).await?;
+        let mut buf = PgArgumentBuffer::default();
+        if self.format == Format::Binary {
+            buf.extend_from_slice(BINARY_HEADER);
+        }
+
+        while let Some(row) = rows.next().await {
+            if let Err(err) = self.encode(row.borrow(), &mut buf) {
+                // COPY нужно завершить, иначе соединение останется посреди протокола
+                copy.abort(err.to_string()).await?;
+                return Err(err);
+            }
+
+            if buf.len() >= self.chunk_size {
+                copy.send(buf.as_slice()).await?;
+                buf.clear();
+            }
+        }
+
+        if self.format == Format::Binary {
+            buf.extend_from_slice(BINARY_TRAILER);
+        }
+        if !buf.is_empty() {
+            copy.send(buf.as_slice()).await?;
+        }
+        let rows = copy.finish().await?;
+        self.resync(conn).await?;
+        Ok(rows)
+    }
+
+    // Одна строка в формате COPY (для CSV - с переводом строки) в конец buf
+    pub fn encode(&self, row: &T, buf: &mut PgArgumentBuffer) -> Result<(), Error> {
+        let start = buf.len();
+        if self.format == Format::Binary {
+            buf.extend_from_slice(&(T::COLUMNS.len() as i16).to_be_bytes());
+        }
+        let mut writer = RowWriter { format: self.format, buf, fields: 0 };
+        row.write(&mut writer);
+        let fields = writer.fields;
+        if fields != T::COLUMNS.len() {
+            buf.truncate(start);
+            return Err(Error::Validation {
+                field: "columns",
+                message: format!("CopyRow::write wrote {} fields, {} has {} columns", fields, self.table, T::COLUMNS.len()),
+            });
+        }
+        if self.format == Format::Csv {
+            buf.push(b'\n');
+        }
+        Ok(())
+    }
+
+    // CSV файл с колонками T::COLUMNS в том же порядке, читается потоком
+    pub async fn load_csv_file(&self, conn: &mut PgConnection, path: impl AsRef<Path>, header: bool) -> Result<u64, Error> {
+        let file = async_std::fs::File::open(path.as_ref()).await?;
+        let mut copy = conn.copy_in_raw(&self.statement(Format::Csv, header)).await?;
+        copy.read_from(file).await?;
+        let rows = copy.finish().await?;
+        self.resync(conn).await?;
+        Ok(rows)
+    }
+
+    async fn resync(&self, conn: &mut PgConnection) -> Result<(), Error> {
+        if self.resync_sequences {
+            for column in T::COLUMNS {
+                sequences::resync(&mut *conn, &self.table, column).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// "todo" -> "todo", public.todo -> "public"."todo"
+pub fn quote_ident(ident: &str) -> String {
+    ident.split('.').map(|part| format!("\"{}\"", part.replace('"', "\"\""))).collect::<Vec<_>>().join(".")
+}
+
+macro_rules! copy_value {
+    ($($ty:ty => |$v:ident| $text:expr;)*) => {$(
+        impl CopyValue for $ty {
+            fn encode_binary(&self, buf: &mut PgArgumentBuffer) -> IsNull {
+                Encode::<sqlx::Postgres>::encode_by_ref(self, buf)
+            }
+            fn to_text(&self) -> Option<String> {
+                let $v = self;
+                Some($text)
+            }
+        }
+    )*};
+}
+
+copy_value! {
+    bool => |v| if *v { "t".into() } else { "f".into() };
+    i16 => |v| v.to_string();
+    i32 => |v| v.to_string();
+    i64 => |v| v.to_string();
+    f32 => |v| float_text(f64::from(*v));
+    f64 => |v| float_text(*v);
+    String => |v| v.clone();
+    &str => |v| v.to_string();
+    chrono::DateTime<chrono::Utc> => |v| v.to_rfc3339();
+    chrono::DateTime<chrono::Local> => |v| v.to_rfc3339();
+    chrono::DateTime<chrono::FixedOffset> => |v| v.to_rfc3339();
+    chrono::NaiveDateTime => |v| v.format("%Y-%m-%d %H:%M:%S%.f").to_string();
+    chrono::NaiveDate => |v| v.to_string();
+    sqlx::types::Uuid => |v| v.to_string();
+}
+
+// Display пишет inf/NaN, Postgres ждёт Infinity/NaN
+fn float_text(v: f64) -> String {
+    match v {
+        v if v == f64::INFINITY => "Infinity".into(),
+        v if v == f64::NEG_INFINITY => "-Infinity".into(),
+        v => v.to_string(),
+    }
+}
+
+impl<V: CopyValue> CopyValue for Option<V> {
+    fn encode_binary(&self, buf: &mut PgArgumentBuffer) -> IsNull {
+        match self {
+            Some(value) => value.encode_binary(buf),
+            None => IsNull::Yes,
+        }
+    }
+    fn to_text(&self) -> Option<String> {
+        self.as_ref().and_then(CopyValue::to_text)
+    }
+}
+
+impl<V: CopyValue> CopyValue for &V {
+    fn encode_binary(&self, buf: &mut PgArgumentBuffer) -> IsNull {
+        (**self).encode_binary(buf)
+    }
+    fn to_text(&self) -> Option<String> {
+        (**self).to_text()
+    }
+}
+
+// INSERT ... SELECT * FROM UNNEST($1::TEXT[], $2::BOOL[], ...) ON CONFLICT ... RETURNING id -
+// много строк одним запросом, параметров столько же, сколько колонок (COPY не умеет ON CONFLICT).
+//
+//     let ids: Vec<i32> = bulk_upsert("todo")
+//         .column("id", ids)
+//         .column("name", names)
+//         .column("checked", checked)
+//         .on_conflict_update(&["id"], &["name", "checked"])
+//         .fetch_ids(&pool)
+//         .await?;
+//
+// Колонки передаются массивами одинаковой длины: i-я строка - i-е элементы. Типизированные id
+// передаются внутренним типом (Vec<i32>), для Vec<TodoId> нет sqlx::Type.
+// fetch_ids возвращает id вставленных и обновлённых строк; пропущенные DO NOTHING не попадают.
+// Один ключ дважды в пачке с DO UPDATE - ошибка всей команды (SQLSTATE 21000, "ON CONFLICT DO UPDATE
+// command cannot affect row a second time"): дубликаты убрать до вызова, оставив последнее значение.
+pub fn bulk_upsert(table: &str) -> BulkUpsert {
+    BulkUpsert {
+        table: quote_ident(table),
+        columns: Vec::new(),
+        arrays: Vec::new(),
+        arguments: PgArguments::default(),
+        rows: None,
+        length_mismatch: None,
+        on_conflict: None,
+        returning: quote_ident("id"),
+    }
+}
+
+// Vec<T> с sqlx::Type для массива (Vec<String>, Vec<i32>, Vec<Option<bool>>, ...)
+pub trait UnnestArray: Encode<'static, Postgres> + Type<Postgres> + Send + 'static {
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> UnnestArray for Vec<T>
+where
+    Vec<T>: Encode<'static, Postgres> + Type<Postgres> + Send + 'static,
+{
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+}
+
+pub struct BulkUpsert {
+    table: String,
+    columns: Vec<String>,
+    // $1::TEXT[]
+    arrays: Vec<String>,
+    arguments: PgArguments,
+    rows: Option<usize>,
+    length_mismatch: Option<String>,
+    on_conflict: Option<String>,
+    returning: String,
+}
+
+impl BulkUpsert {
+    pub fn column<V: UnnestArray>(mut self, name: &str, values: V) -> Self {
+        match self.rows {
+            None => self.rows = Some(values.len()),
+            // UNNEST дополнил бы короткий массив NULL
+            Some(rows) if rows != values.len() && self.length_mismatch.is_none() => {
+                self.length_mismatch = Some(format!("column {} has {} values, expected {}", name, values.len(), rows));
+            }
+            Some(_) => {}
+        }
+        self.arrays.push(format!("${}::{}", self.columns.len() + 1, V::type_info().name()));
+        self.columns.push(quote_ident(name));
+        self.arguments.add(values);
+        self
+    }
+
+    // ON CONFLICT <clause>, например "(id) DO NOTHING"
+    pub fn on_conflict(mut self, clause: &str) -> Self {
+        self.on_conflict = Some(clause.to_string());
+        self
+    }
+
+    // ON CONFLICT (target) DO UPDATE SET column = EXCLUDED.column, ...
+    pub fn on_conflict_update(self, target: &[&str], update: &[&str]) -> Self {
+        let target = target.iter().map(|column| quote_ident(column)).collect::<Vec<_>>().join(", ");
+        let update = update
+            .iter()
+            .map(|column| format!("{0} = EXCLUDED.{0}", quote_ident(column)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.on_conflict(&format!("({}) DO UPDATE SET {}", target, update))
+    }
+
+    // колонка для RETURNING, по умолчанию id
+    pub fn returning(mut self, column: &str) -> Self {
+        self.returning = quote_ident(column);
+        self
+    }
+
+    pub fn sql(&self) -> String {
+        let mut sql = format!(
+            "INSERT INTO {} ({}) SELECT * FROM UNNEST({})",
+            self.table,
+            self.columns.join(", "),
+            self.arrays.join(", ")
+        );
+        if let Some(clause) = &self.on_conflict {
+            sql.push_str(" ON CONFLICT ");
+            sql.push_str(clause);
+        }
+        sql.push_str(" RETURNING ");
+        sql.push_str(&self.returning);
+        sql
+    }
+
+    pub async fn fetch_ids<'e, I, E>(self, executor: E) -> Result<Vec<I>, Error>
+    where
+        I: for<'r> Decode<'r, Postgres> + Type<Postgres> + Send + Unpin,
+        E: Executor<'e, Database = Postgres>,
+    {
+        if let Some(message) = self.length_mismatch {
+            return Err(Error::Validation { field: "columns", message });
+        }
+        if self.rows.unwrap_or(0) == 0 {
+            return Ok(Vec::new());
+        }
+        let sql = self.sql();
+        Ok(sqlx::query_scalar_with(&sql, self.arguments).fetch_all(executor).await?)
+    }
+}
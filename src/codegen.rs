@@ -0,0 +1,42 @@
+use sqlx::postgres::PgPoolOptions;
+use sqlx_example::{model_codegen, schema, settings};
+use std::time::Duration;
+
+const DEFAULT_TABLES: &[&str] = &["todo", "queue", "posts", "users"];
+
+// Сгенерировать #[derive(sqlx::FromRow)] структуры по схеме базы.
+//
+// cargo run --bin codegen                          - todo, queue, posts, users в stdout
+// cargo run --bin codegen -- todo queue            - только указанные таблицы
+// cargo run --bin codegen -- --out src/models.rs   - в файл
+#[tokio::main]
+async fn main() -> Result<(),anyhow::Error> {
+    let mut out: Option<String> = None;
+    let mut tables: Vec<String> = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => out = Some(args.next().ok_or_else(|| anyhow::anyhow!("--out requires a path"))?),
+            _ => tables.push(arg),
+        }
+    }
+    if tables.is_empty() {
+        tables = DEFAULT_TABLES.iter().map(|t| t.to_string()).collect();
+    }
+
+    let config:String = settings::config().expect("Error parse config");
+    let pool = PgPoolOptions::new()
+        .max_connections(1)
+        .max_lifetime(Duration::from_secs(30 * 60))
+        .connect(&config)
+        .await?;
+    let schema = schema::introspect(&pool).await?;
+    pool.close().await;
+
+    let code = model_codegen::generate(&schema, &tables)?;
+    match out {
+        Some(path) => std::fs::write(&path, code)?,
+        None => print!("{}", code),
+    }
+    Ok(())
+}
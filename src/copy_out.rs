@@ -0,0 +1,85 @@
+// COPY ... TO STDOUT таблицы или запроса в CSV, TSV или JSON Lines.
+// copy_out_raw отдаёт поток кусков, каждый сразу пишется в out - память не растёт с размером таблицы.
+
+use crate::bulk::quote_ident;
+use crate::error::Error;
+use futures::StreamExt;
+use sqlx::postgres::PgConnection;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    // с заголовком
+    Csv,
+    // CSV с табуляцией и заголовком (значения с табуляцией/переводом строки - в кавычках)
+    Tsv,
+    // {"id":1,"name":...} на строку
+    JsonLines,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "csv" => Ok(Format::Csv),
+            "tsv" => Ok(Format::Tsv),
+            "jsonl" | "ndjson" => Ok(Format::JsonLines),
+            _ => Err(format!("unknown format {:?}, expected csv, tsv or jsonl", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Source {
+    Table(String),
+    Query(String),
+}
+
+impl Source {
+    fn select(&self) -> String {
+        match self {
+            Source::Table(table) => format!("SELECT * FROM {}", quote_ident(table)),
+            Source::Query(query) => query.trim().trim_end_matches(';').to_string(),
+        }
+    }
+}
+
+pub fn statement(source: &Source, format: Format) -> String {
+    match format {
+        Format::Csv => format!("COPY ({}) TO STDOUT WITH (FORMAT csv, HEADER true)", source.select()),
+        Format::Tsv => format!("COPY ({}) TO STDOUT WITH (FORMAT csv, HEADER true, DELIMITER E'\\t')", source.select()),
+        // текстовый формат COPY удвоил бы обратные слэши внутри JSON, а CSV с обычными кавычками
+        // взял бы строку в кавычки. Кавычка и разделитель - управляющие символы, которые
+        // row_to_json всегда экранирует (\u0001), поэтому строка выходит как есть.
+        Format::JsonLines => format!(
+            "COPY (SELECT row_to_json(export_row) FROM ({}) export_row) TO STDOUT WITH (FORMAT csv, QUOTE E'\\x01', DELIMITER E'\\x02')",
+            source.select()
+        ),
+    }
+}
+
+// Вернуть число записанных байт
+pub async fn export<W: Write>(conn: &mut PgConnection, source: &Source, format: Format, out: &mut W) -> Result<u64, Error> {
+    let mut stream = conn.copy_out_raw(&statement(source, format)).await?;
+    let mut written = 0;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        out.write_all(&chunk)?;
+        written += chunk.len() as u64;
+    }
+    out.flush()?;
+    Ok(written)
+}
+
+// export со сжатием gzip; вернуть число байт до сжатия и out после хвоста gzip.
+// Хвост пишет finish: drop GzEncoder молча теряет ошибку записи и оставляет обрезанный файл.
+pub async fn export_gzip<W: Write>(conn: &mut PgConnection, source: &Source, format: Format, out: W) -> Result<(u64, W), Error> {
+    let mut encoder = GzEncoder::new(out, Compression::default());
+    let written = export(conn, source, format, &mut encoder).await?;
+    let mut out = encoder.finish()?;
+    out.flush()?;
+    Ok((written, out))
+}
@@ -0,0 +1,220 @@
+// Primary и реплики только для чтения (settings: host/port и [[replicas]]):
+//
+//     let db = Db::connect().await?;
+//     let session = db.session();                 // на один запрос пользователя
+//     let todo = repo::Todos(&session).find(id).await?;               // реплика
+//     repo::Todos(&session).set_checked(id, true).await?;             // primary
+//     let todo = repo::Todos(&session).find(id).await?;               // primary: своя запись видна сразу
+//
+// reader() - реплики по кругу, пропуская исключённые; если исправных нет - primary.
+// Реплика исключается на eject_for, когда не отвечает на check_health или когда чтение через
+// read() упало с потерей соединения (тогда чтение повторяется на следующем reader, упавший повтор
+// на реплике исключает и её). Реплики отстают от primary, поэтому Session после успешной записи
+// через write() читает с primary (pin_reads_after_write, по умолчанию да).
+
+use crate::error::Error;
+use crate::settings;
+use futures::future::{join_all, BoxFuture};
+use sqlx::postgres::PgPoolOptions;
+use sqlx::PgPool;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct Replica {
+    pool: PgPool,
+    // None - исправна
+    ejected_until: Mutex<Option<Instant>>,
+}
+
+impl Replica {
+    fn is_healthy(&self, now: Instant) -> bool {
+        match *self.ejected_until.lock().unwrap() {
+            Some(until) => until <= now,
+            None => true,
+        }
+    }
+}
+
+pub struct Db {
+    primary: PgPool,
+    replicas: Vec<Replica>,
+    next: AtomicUsize,
+    eject_for: Duration,
+    health_timeout: Duration,
+    pin_reads_after_write: bool,
+}
+
+impl Db {
+    pub fn new(primary: PgPool, replicas: Vec<PgPool>) -> Self {
+        Db {
+            primary,
+            replicas: replicas.into_iter().map(|pool| Replica { pool, ejected_until: Mutex::new(None) }).collect(),
+            next: AtomicUsize::new(0),
+            eject_for: Duration::from_secs(30),
+            health_timeout: Duration::from_secs(1),
+            pin_reads_after_write: true,
+        }
+    }
+
+    // Пулы из settings; реплики подключаются лениво, недоступная реплика не мешает старту
+    pub async fn connect() -> Result<Self, Error> {
+        let primary = PgPoolOptions::new()
+            .max_connections(5)
+            .max_lifetime(Duration::from_secs(30 * 60))
+            .connect_with(settings::connect_options()?)
+            .await
+            .map_err(Error::ConnectingToDatabase)?;
+        let replicas = settings::replica_connect_options()?
+            .into_iter()
+            .map(|options| {
+                PgPoolOptions::new()
+                    .max_connections(5)
+                    .max_lifetime(Duration::from_secs(30 * 60))
+                    .connect_timeout(Duration::from_secs(2))
+                    .connect_lazy_with(options)
+            })
+            .collect();
+        Ok(Db::new(primary, replicas))
+    }
+
+    pub fn eject_for(mut self, eject_for: Duration) -> Self {
+        self.eject_for = eject_for;
+        self
+    }
+
+    // сколько check_health ждёт ответа реплики
+    pub fn health_timeout(mut self, health_timeout: Duration) -> Self {
+        self.health_timeout = health_timeout;
+        self
+    }
+
+    pub fn pin_reads_after_write(mut self, pin: bool) -> Self {
+        self.pin_reads_after_write = pin;
+        self
+    }
+
+    pub fn writer(&self) -> &PgPool {
+        &self.primary
+    }
+
+    pub fn reader(&self) -> &PgPool {
+        if self.replicas.is_empty() {
+            return &self.primary;
+        }
+        let now = Instant::now();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        (0..self.replicas.len())
+            .map(|offset| &self.replicas[(start + offset) % self.replicas.len()])
+            .find(|replica| replica.is_healthy(now))
+            .map_or(&self.primary, |replica| &replica.pool)
+    }
+
+    pub fn replica(&self, index: usize) -> Option<&PgPool> {
+        self.replicas.get(index).map(|replica| &replica.pool)
+    }
+
+    pub fn healthy_replicas(&self) -> usize {
+        let now = Instant::now();
+        self.replicas.iter().filter(|replica| replica.is_healthy(now)).count()
+    }
+
+    // Исключить реплику на eject_for; pool - из reader() или replica(); primary не исключается
+    pub fn eject(&self, pool: &PgPool) {
+        if let Some(replica) = self.replicas.iter().find(|replica| std::ptr::eq(&replica.pool, pool)) {
+            *replica.ejected_until.lock().unwrap() = Some(Instant::now() + self.eject_for);
+        }
+    }
+
+    // Чтение на reader(); при потере соединения с репликой она исключается и чтение повторяется один раз.
+    // Future может заимствовать не только pool ('f короче 'a): |pool| Box::pin(search(pool, &filter))
+    pub async fn read<'a, 'f, T, F>(&'a self, f: F) -> Result<T, Error>
+    where
+        F: Fn(&'a PgPool) -> BoxFuture<'f, Result<T, Error>>,
+    {
+        let pool = self.reader();
+        match f(pool).await {
+            Err(err) if self.is_lost_replica(pool, &err) => {
+                self.eject(pool);
+                let pool = self.reader();
+                let result = f(pool).await;
+                if matches!(&result, Err(err) if self.is_lost_replica(pool, err)) {
+                    self.eject(pool);
+                }
+                result
+            }
+            result => result,
+        }
+    }
+
+    fn is_lost_replica(&self, pool: &PgPool, err: &Error) -> bool {
+        matches!(err, Error::ConnectionLost(_) | Error::ConnectingToDatabase(_)) && !std::ptr::eq(pool, &self.primary)
+    }
+
+    // SELECT 1 на каждой реплике: не ответила за health_timeout - исключена, ответила - снова в работе
+    pub async fn check_health(&self) {
+        let checks = self.replicas.iter().map(|replica| async move {
+            let ping = sqlx::query("SELECT 1").execute(&replica.pool);
+            let healthy = matches!(async_std::future::timeout(self.health_timeout, ping).await, Ok(Ok(_)));
+            *replica.ejected_until.lock().unwrap() = if healthy { None } else { Some(Instant::now() + self.eject_for) };
+        });
+        join_all(checks).await;
+    }
+
+    // check_health в цикле: tokio::spawn(async move { db.run_health_checks(interval).await })
+    pub async fn run_health_checks(&self, interval: Duration) {
+        loop {
+            self.check_health().await;
+            async_std::task::sleep(interval).await;
+        }
+    }
+
+    // Контекст одного запроса пользователя
+    pub fn session(&self) -> Session<'_> {
+        Session { db: self, wrote: AtomicBool::new(false) }
+    }
+}
+
+pub struct Session<'a> {
+    db: &'a Db,
+    wrote: AtomicBool,
+}
+
+impl<'a> Session<'a> {
+    // после успешного write() - primary, если включён pin_reads_after_write
+    pub fn reader(&self) -> &'a PgPool {
+        if self.is_pinned() {
+            self.db.writer()
+        } else {
+            self.db.reader()
+        }
+    }
+
+    // Запись на primary; чтения закрепляются только если она прошла - после ошибки реплики
+    // отстают не больше обычного
+    pub async fn write<'f, T, F>(&self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&'a PgPool) -> BoxFuture<'f, Result<T, Error>>,
+    {
+        let result = f(self.db.writer()).await;
+        if result.is_ok() {
+            self.wrote.store(true, Ordering::Relaxed);
+        }
+        result
+    }
+
+    pub fn is_pinned(&self) -> bool {
+        self.db.pin_reads_after_write && self.wrote.load(Ordering::Relaxed)
+    }
+
+    pub async fn read<'f, T, F>(&self, f: F) -> Result<T, Error>
+    where
+        F: Fn(&'a PgPool) -> BoxFuture<'f, Result<T, Error>>,
+    {
+        if self.is_pinned() {
+            f(self.db.writer()).await
+        } else {
+            self.db.read(f).await
+        }
+    }
+}
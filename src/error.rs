@@ -0,0 +1,138 @@
+// Общая ошибка библиотеки и бинарников. Исходная ошибка (sqlx::Error, MigrateError, ConfigError)
+// сохраняется как source, anyhow печатает всю цепочку: `{:#}` или `{:?}`.
+// Ошибки базы разобраны по SQLSTATE, поля table/column/constraint - из PgDatabaseError.
+
+use crate::message;
+use config::ConfigError;
+use sqlx::migrate::MigrateError;
+use sqlx::postgres::PgDatabaseError;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Bad config")]
+    BadConfig(#[from] ConfigError),
+    #[error("Connecting to database")]
+    ConnectingToDatabase(#[source] sqlx::Error),
+    #[error("Internal error")]
+    Internal(#[source] sqlx::Error),
+    #[error("Not found: {0}")]
+    NotFound(String),
+    #[error("Migrating database")]
+    DatabaseMigration(#[from] MigrateError),
+    #[error("I/O error")]
+    Io(#[from] std::io::Error),
+    #[error("Validation: {field} {message}")]
+    Validation { field: &'static str, message: String },
+    #[error(transparent)]
+    Message(message::Error),
+    #[error("Unique violation")]
+    UniqueViolation(#[source] Box<DatabaseError>),
+    #[error("Foreign key violation")]
+    ForeignKeyViolation(#[source] Box<DatabaseError>),
+    #[error("Check violation")]
+    CheckViolation(#[source] Box<DatabaseError>),
+    #[error("Serialization failure")]
+    SerializationFailure(#[source] Box<DatabaseError>),
+    #[error("Deadlock detected")]
+    Deadlock(#[source] Box<DatabaseError>),
+    #[error("Query canceled")]
+    QueryCanceled(#[source] Box<DatabaseError>),
+    #[error("Connection lost")]
+    ConnectionLost(#[source] sqlx::Error),
+    // остальные SQLSTATE
+    #[error("Database error")]
+    Database(#[source] Box<DatabaseError>),
+}
+
+// Ошибка Postgres с полями из PgDatabaseError (table/column/constraint есть не у всех ошибок)
+#[derive(thiserror::Error, Debug)]
+#[error("{message} (SQLSTATE {code})")]
+pub struct DatabaseError {
+    pub code: String,
+    pub message: String,
+    pub table: Option<String>,
+    pub column: Option<String>,
+    pub constraint: Option<String>,
+    #[source]
+    pub source: sqlx::Error,
+}
+
+impl Error {
+    // Повтор той же операции (транзакции целиком) безопасен и может пройти: Postgres откатил её
+    // сам (конфликт сериализации, deadlock) или она не начиналась (нет свободного соединения).
+    // Нарушения ограничений и валидация - нет. Обрыв соединения сюда не входит, см. is_connection_lost.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Error::SerializationFailure(_) | Error::Deadlock(_) | Error::ConnectingToDatabase(sqlx::Error::PoolTimedOut)
+        )
+    }
+
+    // Соединение оборвалось посреди запроса: неизвестно, применился ли он (COMMIT мог пройти).
+    // Повторять можно только идемпотентные операции - решает вызывающий.
+    pub fn is_connection_lost(&self) -> bool {
+        matches!(self, Error::ConnectionLost(_))
+    }
+
+    // SQLSTATE и поля ошибки Postgres, если она есть
+    pub fn database_error(&self) -> Option<&DatabaseError> {
+        match self {
+            Error::UniqueViolation(err)
+            | Error::ForeignKeyViolation(err)
+            | Error::CheckViolation(err)
+            | Error::SerializationFailure(err)
+            | Error::Deadlock(err)
+            | Error::QueryCanceled(err)
+            | Error::Database(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(err: sqlx::Error) -> Self {
+        let pg = match &err {
+            sqlx::Error::RowNotFound => return Error::NotFound("row not found".into()),
+            sqlx::Error::Io(_) | sqlx::Error::Tls(_) => return Error::ConnectionLost(err),
+            sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed => return Error::ConnectingToDatabase(err),
+            sqlx::Error::Database(db) => match db.try_downcast_ref::<PgDatabaseError>() {
+                Some(pg) => pg,
+                None => return Error::Internal(err),
+            },
+            _ => return Error::Internal(err),
+        };
+
+        // https://www.postgresql.org/docs/current/errcodes-appendix.html
+        let code = pg.code().to_string();
+        if code.starts_with("08") || matches!(code.as_str(), "57P01" | "57P02" | "57P03") {
+            // connection_exception, admin_shutdown, crash_shutdown, cannot_connect_now
+            return Error::ConnectionLost(err);
+        }
+        let wrap = match code.as_str() {
+            "23505" => Error::UniqueViolation,
+            "23503" => Error::ForeignKeyViolation,
+            "23514" => Error::CheckViolation,
+            "40001" => Error::SerializationFailure,
+            "40P01" => Error::Deadlock,
+            "57014" => Error::QueryCanceled,
+            _ => Error::Database,
+        };
+        wrap(Box::new(DatabaseError {
+            message: pg.message().to_string(),
+            table: pg.table().map(String::from),
+            column: pg.column().map(String::from),
+            constraint: pg.constraint().map(String::from),
+            code,
+            source: err,
+        }))
+    }
+}
+
+impl From<message::Error> for Error {
+    fn from(err: message::Error) -> Self {
+        match err {
+            message::Error::Database(err) => err.into(),
+            err => Error::Message(err),
+        }
+    }
+}
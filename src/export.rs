@@ -0,0 +1,46 @@
+use sqlx::Connection;
+use sqlx_example::copy_out::{self, Format, Source};
+use sqlx_example::settings;
+use std::fs::File;
+use std::io::BufWriter;
+
+// Выгрузить таблицу или запрос через COPY TO STDOUT.
+//
+// cargo run --bin export -- todo                                   - CSV в stdout
+// cargo run --bin export -- todo --format jsonl --out todo.jsonl   - JSON Lines в файл
+// cargo run --bin export -- --query "SELECT id, name FROM todo WHERE checked" --format tsv
+// cargo run --bin export -- todo --out todo.csv.gz                 - .gz или --gzip сжимает вывод
+#[tokio::main]
+async fn main() -> Result<(),anyhow::Error> {
+    let mut source: Option<Source> = None;
+    let mut format = Format::Csv;
+    let mut out: Option<String> = None;
+    let mut gzip = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--query" => source = Some(Source::Query(args.next().ok_or_else(|| anyhow::anyhow!("--query requires SQL"))?)),
+            "--format" => format = args.next().ok_or_else(|| anyhow::anyhow!("--format requires csv, tsv or jsonl"))?.parse().map_err(anyhow::Error::msg)?,
+            "--out" => out = Some(args.next().ok_or_else(|| anyhow::anyhow!("--out requires a path"))?),
+            "--gzip" => gzip = true,
+            _ => source = Some(Source::Table(arg)),
+        }
+    }
+    let source = source.ok_or_else(|| anyhow::anyhow!("usage: export <table> | --query SQL [--format csv|tsv|jsonl] [--out path] [--gzip]"))?;
+
+    let mut conn = sqlx::PgConnection::connect(&settings::config()?).await?;
+    // конкретные типы вместо Box<dyn Write> и drop: ошибки хвоста gzip и flush доходят до кода выхода
+    let written = match &out {
+        Some(path) if gzip || path.ends_with(".gz") => {
+            copy_out::export_gzip(&mut conn, &source, format, BufWriter::new(File::create(path)?)).await?.0
+        }
+        // export делает flush
+        Some(path) => copy_out::export(&mut conn, &source, format, &mut BufWriter::new(File::create(path)?)).await?,
+        None if gzip => copy_out::export_gzip(&mut conn, &source, format, std::io::stdout()).await?.0,
+        None => copy_out::export(&mut conn, &source, format, &mut std::io::stdout()).await?,
+    };
+    if let Some(path) = out {
+        eprintln!("{} bytes of {:?} written to {}", written, format, path);
+    }
+    Ok(())
+}
@@ -0,0 +1,40 @@
+use sqlx::Connection;
+use sqlx_example::{sequences, settings};
+
+// Поднять последовательности SERIAL/IDENTITY колонок до max(id) во всех таблицах.
+//
+// cargo run --bin fix-sequences              - исправить отстающие
+// cargo run --bin fix-sequences -- --dry-run - только показать (код выхода 1, если есть отстающие)
+#[tokio::main]
+async fn main() -> Result<(),anyhow::Error> {
+    let dry_run = std::env::args().skip(1).any(|arg| arg == "--dry-run");
+
+    let mut conn = sqlx::PgConnection::connect(&settings::config()?).await?;
+    let fixes = sequences::resync_all(&mut conn, dry_run).await?;
+    conn.close().await?;
+
+    let mut behind = 0;
+    for fix in &fixes {
+        let status = match (fix.is_behind(), dry_run) {
+            (false, _) => "ok",
+            (true, true) => "behind",
+            (true, false) => "fixed",
+        };
+        if fix.is_behind() {
+            behind += 1;
+        }
+        println!(
+            "{:<6} {}.{} ({}) last_value: {} max: {}",
+            status,
+            fix.table,
+            fix.column,
+            fix.sequence,
+            fix.last_value.map_or("-".to_string(), |v| v.to_string()),
+            fix.max.map_or("-".to_string(), |v| v.to_string())
+        );
+    }
+    if dry_run && behind > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,112 @@
+// Свой тип id для каждой таблицы: TodoId нельзя передать туда, где ждут JobId.
+// Внутренний тип (i32 - INT4, i64 - INT8, Uuid - UUID) определяет Type/Encode/Decode,
+// serde сериализует id как внутреннее значение (1, "67e5...").
+
+use sqlx::types::Uuid;
+
+#[doc(hidden)]
+pub use serde as __serde;
+
+// typed_id!(pub struct OrderId(i64));
+#[macro_export]
+macro_rules! typed_id {
+    ($(#[$meta:meta])* $vis:vis struct $name:ident($inner:ty);) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+        $vis struct $name(pub $inner);
+
+        impl sqlx::Type<sqlx::Postgres> for $name {
+            fn type_info() -> sqlx::postgres::PgTypeInfo {
+                <$inner as sqlx::Type<sqlx::Postgres>>::type_info()
+            }
+            fn compatible(ty: &sqlx::postgres::PgTypeInfo) -> bool {
+                <$inner as sqlx::Type<sqlx::Postgres>>::compatible(ty)
+            }
+        }
+
+        impl<'q> sqlx::Encode<'q, sqlx::Postgres> for $name {
+            fn encode_by_ref(&self, buf: &mut sqlx::postgres::PgArgumentBuffer) -> sqlx::encode::IsNull {
+                <$inner as sqlx::Encode<'q, sqlx::Postgres>>::encode_by_ref(&self.0, buf)
+            }
+        }
+
+        impl<'r> sqlx::Decode<'r, sqlx::Postgres> for $name {
+            fn decode(value: sqlx::postgres::PgValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+                <$inner as sqlx::Decode<'r, sqlx::Postgres>>::decode(value).map($name)
+            }
+        }
+
+        // query_as::<_, TodoId>("... RETURNING id") - первая колонка
+        impl<'r> sqlx::FromRow<'r, sqlx::postgres::PgRow> for $name {
+            fn from_row(row: &'r sqlx::postgres::PgRow) -> sqlx::Result<Self> {
+                sqlx::Row::try_get(row, 0)
+            }
+        }
+
+        impl $crate::ids::__serde::Serialize for $name {
+            fn serialize<S: $crate::ids::__serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                $crate::ids::__serde::Serialize::serialize(&self.0, serializer)
+            }
+        }
+
+        impl<'de> $crate::ids::__serde::Deserialize<'de> for $name {
+            fn deserialize<D: $crate::ids::__serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                <$inner as $crate::ids::__serde::Deserialize<'de>>::deserialize(deserializer).map($name)
+            }
+        }
+
+        impl From<$inner> for $name {
+            fn from(id: $inner) -> Self {
+                $name(id)
+            }
+        }
+
+        impl From<$name> for $inner {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+
+        // BulkLoader: COPY как внутреннее значение
+        impl $crate::bulk::CopyValue for $name {
+            fn encode_binary(&self, buf: &mut sqlx::postgres::PgArgumentBuffer) -> sqlx::encode::IsNull {
+                $crate::bulk::CopyValue::encode_binary(&self.0, buf)
+            }
+            fn to_text(&self) -> Option<String> {
+                $crate::bulk::CopyValue::to_text(&self.0)
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                std::fmt::Display::fmt(&self.0, f)
+            }
+        }
+    };
+}
+
+typed_id! {
+    // todo.id SERIAL
+    pub struct TodoId(i32);
+}
+
+typed_id! {
+    // queue.id UUID
+    pub struct JobId(Uuid);
+}
+
+typed_id! {
+    // posts.id SERIAL
+    pub struct PostId(i32);
+}
+
+typed_id! {
+    // users.id SERIAL
+    pub struct UserId(i32);
+}
+
+impl JobId {
+    pub fn new() -> Self {
+        JobId(Uuid::new_v4())
+    }
+}
@@ -0,0 +1,68 @@
+use sqlx::Connection;
+use sqlx_example::settings;
+use sqlx_example::staging::{Column, OnConflict, StagingImport};
+use std::path::{Path, PathBuf};
+
+// Импорт CSV в todo через staging таблицу: плохие строки уходят в report, остальные вставляются.
+// Колонки файла берутся из заголовка (id, name, created_at, checked_date, checked в любом порядке).
+//
+// cargo run --bin import -- todo.csv                          - отказы в todo.csv.rejected.csv
+// cargo run --bin import -- todo.csv --report rejected.csv
+// cargo run --bin import -- todo.csv --update                 - существующий id обновить (по умолчанию - отказ)
+// cargo run --bin import -- todo.csv --skip-existing          - существующий id пропустить
+#[tokio::main]
+async fn main() -> Result<(),anyhow::Error> {
+    let mut csv: Option<PathBuf> = None;
+    let mut report: Option<PathBuf> = None;
+    let mut on_conflict = OnConflict::Reject;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--report" => report = Some(args.next().ok_or_else(|| anyhow::anyhow!("--report requires a path"))?.into()),
+            "--update" => on_conflict = OnConflict::Update,
+            "--skip-existing" => on_conflict = OnConflict::Skip,
+            _ => csv = Some(arg.into()),
+        }
+    }
+    let csv = csv.ok_or_else(|| anyhow::anyhow!("usage: import <file.csv> [--report path] [--update | --skip-existing]"))?;
+    let report = report.unwrap_or_else(|| PathBuf::from(format!("{}.rejected.csv", csv.display())));
+
+    let columns = header(&csv)?
+        .iter()
+        .map(|name| todo_column(name).ok_or_else(|| anyhow::anyhow!("unknown todo column {:?} in header", name)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let has_id = columns.iter().any(|column| column.name == "id");
+    let mut import = StagingImport::new("todo", columns).on_conflict(on_conflict);
+    if has_id {
+        import = import.key(&["id"]);
+    }
+
+    let mut conn = sqlx::PgConnection::connect(&settings::config()?).await?;
+    let result = import.run(&mut conn, &csv, Some(&report)).await?;
+    println!("{} rows: {} imported, {} rejected", result.total, result.imported, result.rejected);
+    if result.rejected > 0 {
+        println!("rejected rows: {}", report.display());
+    } else {
+        std::fs::remove_file(&report)?;
+    }
+    Ok(())
+}
+
+fn todo_column(name: &str) -> Option<Column> {
+    Some(match name {
+        "id" => Column::new("id", "INT4"),
+        "name" => Column::new("name", "VARCHAR").required().max_length(255),
+        "created_at" => Column::new("created_at", "TIMESTAMPTZ"),
+        "checked_date" => Column::new("checked_date", "TIMESTAMPTZ"),
+        "checked" => Column::new("checked", "BOOLEAN"),
+        _ => return None,
+    })
+}
+
+// заголовок - простые имена через запятую
+fn header(csv: &Path) -> Result<Vec<String>, anyhow::Error> {
+    use std::io::BufRead;
+    let mut line = String::new();
+    std::io::BufReader::new(std::fs::File::open(csv)?).read_line(&mut line)?;
+    Ok(line.trim().split(',').map(|name| name.trim().trim_matches('"').to_string()).collect())
+}
@@ -4,6 +4,7 @@ pub mod settings{
     use std::sync::RwLock;
     use lazy_static::lazy_static;
     use sqlx::postgres::PgConnectOptions;
+    use crate::error::Error;
 
     lazy_static! {
         static ref SETTINGS: RwLock<Config> = {
@@ -15,24 +16,123 @@ pub mod settings{
         };
     }
 
-   pub fn config() -> Result<String, Box<dyn std::error::Error>>{
+   fn get<'de, T: serde::Deserialize<'de>>(key: &str) -> Result<T, Error>{
+        let settings = SETTINGS.read().map_err(|_| config::ConfigError::Message("settings lock poisoned".into()))?;
+        Ok(settings.get::<T>(key)?)
+   }
+
+   // необязательный ключ: нет в settings.toml и APP_* - default
+   fn get_or<'de, T: serde::Deserialize<'de>>(key: &str, default: T) -> Result<T, Error>{
+        match get::<T>(key){
+            Err(Error::BadConfig(config::ConfigError::NotFound(_))) => Ok(default),
+            value => value,
+        }
+   }
+
+   // URL для connect(&str); с pgbouncer - без кэша подготовленных запросов, как connect_options
+   pub fn config() -> Result<String, Error>{
+        let config = config_for_dbname(&dbname()?)?;
+        Ok(if pgbouncer()? { format!("{}?statement-cache-capacity=0", config) } else { config })
+   } 
+
+   // тот же сервер, другая база (например временная для schema-diff)
+   pub fn config_for_dbname(dbname: &str) -> Result<String, Error>{
+        url(&get::<String>("host")?, get::<u16>("port")?, dbname)
+   } 
+
+   // другая база напрямую в Postgres: pgbouncer знает только базы из своего [databases]
+   pub fn direct_config_for_dbname(dbname: &str) -> Result<String, Error>{
+        url(&get_or("direct_host", get::<String>("host")?)?, get_or("direct_port", get::<u16>("port")?)?, dbname)
+   }
+
+   fn url(host: &str, port: u16, dbname: &str) -> Result<String, Error>{
         let config = format!("postgres://{user}:{password}@{host}:{port}/{dbname}",
-        host=SETTINGS.read()?.get::<String>("host")?,
-        user=SETTINGS.read()?.get::<String>("user")?,
-        port=SETTINGS.read()?.get::<i32>("port")?,
-        password=SETTINGS.read()?.get::<String>("password")?,
-        dbname=SETTINGS.read()?.get::<String>("dbname")?);
+        host=host,
+        user=get::<String>("user")?,
+        port=port,
+        password=get::<String>("password")?,
+        dbname=dbname);
         Ok(config)
-   } 
+   }
+
+   pub fn dbname() -> Result<String, Error>{
+        get::<String>("dbname")
+   }
 
-   pub fn config_2() -> Result<PgConnectOptions, Box<dyn std::error::Error>>{    
+   pub fn config_2() -> Result<PgConnectOptions, Error>{    
         let pg_conn_option = PgConnectOptions::new()
-        .host(&SETTINGS.read()?.get::<String>("host")?)
-        .port(SETTINGS.read()?.get::<u16>("port")?)
-        .username(&SETTINGS.read()?.get::<String>("user")?)
-        .password(&SETTINGS.read()?.get::<String>("password")?)
+        .host(&get::<String>("host")?)
+        .port(get::<u16>("port")?)
+        .username(&get::<String>("user")?)
+        .password(&get::<String>("password")?)
         .ssl_mode(sqlx::postgres::PgSslMode::Disable);
 
     Ok(pg_conn_option)
    } 
-}
\ No newline at end of file
+
+   // host/port указывают на pgbouncer с pool_mode=transaction (APP_PGBOUNCER=true)
+   pub fn pgbouncer() -> Result<bool, Error>{
+        get_or("pgbouncer", false)
+   }
+
+   // statement_cache_capacity соединений из connect_options без pgbouncer (столько же по умолчанию в sqlx)
+   pub const STATEMENT_CACHE_CAPACITY: usize = 100;
+
+   // Для пула. С pgbouncer кэш подготовленных запросов выключен: ни один запрос не остаётся
+   // подготовленным на соединении, как при persistent(false) (у query!/query_as! и QueryAs в sqlx 0.5
+   // этого флага нет, кэш - единственный способ для всех запросов сразу), и следующая транзакция
+   // на другом серверном соединении не рассчитывает на prepare из предыдущей.
+   // Parse при этом всё равно именованный (sqlx_s_N) и не закрывается, поэтому нужен
+   // pgbouncer 1.21+ с max_prepared_statements > 0.
+   pub fn connect_options() -> Result<PgConnectOptions, Error>{
+        let options = config_2()?.database(&dbname()?);
+        Ok(options.statement_cache_capacity(if pgbouncer()? { 0 } else { STATEMENT_CACHE_CAPACITY }))
+   }
+
+   #[derive(serde::Deserialize)]
+   struct Replica{
+        host: String,
+        port: u16,
+   }
+
+   // [[replicas]] в settings.toml: host/port реплики, остальное как у primary (connect_options)
+   pub fn replica_connect_options() -> Result<Vec<PgConnectOptions>, Error>{
+        let options = connect_options()?;
+        Ok(get_or::<Vec<Replica>>("replicas", Vec::new())?
+            .into_iter()
+            .map(|replica| options.clone().host(&replica.host).port(replica.port))
+            .collect())
+   }
+
+   // Прямое соединение с Postgres мимо pgbouncer (direct_host/direct_port, по умолчанию host/port)
+   // для состояния сессии: LISTEN, advisory lock миграций
+   pub fn direct_connect_options() -> Result<PgConnectOptions, Error>{
+        Ok(config_2()?
+            .database(&dbname()?)
+            .host(&get_or("direct_host", get::<String>("host")?)?)
+            .port(get_or("direct_port", get::<u16>("port")?)?))
+   }
+}
+
+pub use sqlx_example_derive::SqlEnum;
+pub use error::Error;
+
+pub mod error;
+pub mod ids;
+pub mod message;
+pub mod schema;
+pub mod sql_script;
+pub mod migration_lint;
+pub mod migrate;
+pub mod transaction;
+pub mod outbox;
+pub mod bulk;
+pub mod copy_out;
+pub mod model_codegen;
+pub mod sequences;
+pub mod staging;
+pub mod query_builder;
+pub mod repo;
+pub mod statements;
+pub mod listener;
+pub mod db;
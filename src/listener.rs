@@ -0,0 +1,17 @@
+// LISTEN - состояние сессии: через pgbouncer в pool_mode=transaction подписка осталась бы на
+// серверном соединении, которое уже обслуживает другого клиента. Поэтому PgListener всегда
+// получает своё прямое соединение с Postgres (settings::direct_connect_options), а NOTIFY
+// можно отправлять и через pgbouncer.
+
+use crate::error::Error;
+use crate::settings;
+use sqlx::postgres::{PgListener, PgPoolOptions};
+
+pub async fn connect() -> Result<PgListener, Error> {
+    let pool = PgPoolOptions::new()
+        .max_connections(1)
+        .connect_with(settings::direct_connect_options()?)
+        .await
+        .map_err(Error::ConnectingToDatabase)?;
+    Ok(PgListener::connect_with(&pool).await?)
+}
@@ -1,11 +1,10 @@
 
 use sqlx::Connection;
 use sqlx::Executor;
-use sqlx::Statement;
-use sqlx::postgres::PgPoolOptions;
 use sqlx::postgres::PgConnectOptions;
 use sqlx_example::settings;
-use std::convert::TryFrom;
+use sqlx_example::Error;
+use sqlx_example::statements::{Preparation, Statements};
 use std::time::Duration;
 
 #[tokio::main]
@@ -16,13 +15,14 @@ async fn main() -> Result<(),anyhow::Error> {
     //  etc.
 
     // conn через PgPoolOptions или sqlx::pool::PoolOptions::<sqlx::postgres::Postgres>::new()
-    let config:String = settings::config().expect("Error parse config");
-    let mut pool:sqlx::Pool<sqlx::Postgres> = sqlx::postgres::PgPoolOptions::new()
+    // с pgbouncer = true в settings.toml - без кэша подготовленных запросов
+    let options:PgConnectOptions = settings::connect_options().expect("Error parse config");
+    let pool:sqlx::Pool<sqlx::Postgres> = sqlx::postgres::PgPoolOptions::new()
         .max_connections(5)
         .max_lifetime(Duration::from_secs(30 * 60))
-        .connect(&config)
+        .connect_with(options)
         .await
-        .map_err(|err| self::Error::ConnectingToDatabase(err.to_string()))?;
+        .map_err(self::Error::ConnectingToDatabase)?;
     
     /*
     // conn через PgConnectOptions
@@ -40,7 +40,17 @@ async fn main() -> Result<(),anyhow::Error> {
      error_example(&pool).await?;
 
     // transaction ----------------------------------------------------------------------------------------------------------------------------
-     transaction_example(&pool).await?;
+     let statements = statements()?;
+     transaction_example(&pool, &statements).await?;
+     for stats in statements.stats() {
+         println!("statement {}: hits:{} misses:{} unknown:{}", stats.name, stats.hits, stats.misses, stats.unknown);
+     }
+
+    // outbox ---------------------------------------------------------------------------------------------------------------------------------
+     outbox_example(&pool).await?;
+
+    // primary / реплики ----------------------------------------------------------------------------------------------------------------------
+     replica_example().await?;
 
     // listener -------------------------------------------------------------------------------------------------------------------------------
     test_listener_cleanup().await?;
@@ -48,9 +58,12 @@ async fn main() -> Result<(),anyhow::Error> {
     // COPY IN Postgres
     copy_in_example().await?;
 
+    // queue, JSONB message -------------------------------------------------------------------------------------------------------------------
+    queue_example(&pool).await?;
+
    
     tokio::time::sleep(Duration::from_secs(2)).await;
-    pool.close();
+    pool.close().await;
 
     Ok(())
 }
@@ -65,17 +78,119 @@ where
 
 
 async fn copy_in_example() -> anyhow::Result<()> {
+    use sqlx_example::bulk::{BulkLoader, CopyRow, Format, RowWriter};
+    use chrono::{DateTime, Utc};
+
+    // строка COPY todo без id - id берётся из SERIAL
+    struct NewTodo {
+        name: String,
+        created_at: DateTime<Utc>,
+        checked_date: DateTime<Utc>,
+        checked: bool,
+    }
+    impl CopyRow for NewTodo {
+        const COLUMNS: &'static [&'static str] = &["name", "created_at", "checked_date", "checked"];
+        fn write(&self, row: &mut RowWriter<'_>) {
+            row.field(&self.name);
+            row.field(&self.created_at);
+            row.field(&self.checked_date);
+            row.field(&self.checked);
+        }
+    }
+
     let mut conn = new::<sqlx::postgres::Postgres>().await?;
+    let now = Utc::now();
+    let todos = vec![
+        NewTodo{ name: "jim".into(), created_at: now, checked_date: now, checked: true },
+        NewTodo{ name: "jim, \"the cat\"\n".into(), created_at: now, checked_date: now, checked: true },
+    ];
+
+    // COPY todo (name, created_at, checked_date, checked) FROM STDIN WITH (FORMAT binary)
+    let rows = BulkLoader::<NewTodo>::new("todo").load(&mut conn, &todos).await?;
+    println!("copy binary:{}",rows);
+
+    // то же в CSV с экранированием
+    let rows = BulkLoader::<NewTodo>::new("todo").format(Format::Csv).load(&mut conn, &todos).await?;
+    println!("copy csv:{}",rows);
+
+    // явные id (например перенос из другой базы) - SERIAL последовательность todo_id_seq
+    // после COPY поднимается до max(id), иначе следующий обычный INSERT получил бы duplicate key
+    struct TodoWithId {
+        id: TodoId,
+        name: String,
+    }
+    impl CopyRow for TodoWithId {
+        const COLUMNS: &'static [&'static str] = &["id", "name"];
+        fn write(&self, row: &mut RowWriter<'_>) {
+            row.field(&self.id);
+            row.field(&self.name);
+        }
+    }
+    let max: i32 = sqlx::query_scalar("SELECT max(id) FROM todo").fetch_one(&mut conn).await?;
+    let todos = vec![
+        TodoWithId{ id: TodoId(max + 1), name: "jim".into() },
+        TodoWithId{ id: TodoId(max + 2), name: "jim".into() },
+    ];
+    let rows = BulkLoader::<TodoWithId>::new("todo").load(&mut conn, &todos).await?;
+    let id: TodoId = sqlx::query_as("INSERT INTO todo (name) VALUES ('after copy') RETURNING id").fetch_one(&mut conn).await?;
+    println!("copy with ids:{} next id:{}",rows,id);
 
-    let mut sink/*:sqlx::postgres::copy::PgCopyIn<&mut sqlx::PgConnection>*/ = conn.copy_in_raw("COPY todo FROM STDIN WITH DELIMITER E'\t' CSV").await?;
-  
-    let reader =  String::from("51\tjim\t2021-10-18 23:40:52.319 +0300\t2021-10-18 23:40:52.319 +0300\t1
-    52\tjim\t2021-10-18 23:40:52.319 +0300\t2021-10-18 23:40:52.319 +0300\t1").into_bytes();
-    sink.send(reader).await.unwrap();
-  
-    let rows = sink.finish().await.unwrap();
-    println!("{}",rows);
+    Ok(())
+}
+
+async fn queue_example(pool: &sqlx::Pool<sqlx::Postgres>) -> Result<(),anyhow::Error>{
+    use sqlx_example::message::{self, Envelope, Payload, Registry};
+    use serde::{Serialize, Deserialize};
+    use chrono::Utc;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct SendEmail {
+        to: String,
+        subject: String,
+    }
+    impl Payload for SendEmail {
+        const TYPE: &'static str = "send_email";
+        const VERSION: u32 = 1;
+    }
+
+    #[derive(Debug)]
+    enum Job {
+        SendEmail(SendEmail),
+    }
+
+    // queue.message = {"type":"send_email","version":1,"payload":{"to":...,"subject":...}}
+    let registry = Registry::new().register(Job::SendEmail);
+    let id = message::enqueue(pool, &SendEmail{ to: "cat@example.com".into(), subject: "pet me".into() }, Utc::now()).await?;
+    let job = message::load(pool, id).await?;
+    match registry.decode(&job.message).map_err(self::Error::from)? {
+        Job::SendEmail(email) => println!("job {} send email to {}: {}", job.id, email.to, email.subject),
+    }
 
+    // неизвестный тип или версия - Error, а не паника
+    let unknown = Envelope{ kind: "resize_image".into(), version: 1, payload: serde_json::json!({}) };
+    if let Err(err) = registry.decode(&unknown).map_err(self::Error::from) {
+        println!("{}", err);// unknown message type "resize_image"
+    }
+
+    Ok(())
+}
+
+// чтение на репликах из [[replicas]] (без них - на primary), после записи в сессии - на primary
+async fn replica_example() -> anyhow::Result<()> {
+    use sqlx_example::db::Db;
+    use sqlx_example::repo::Todos;
+
+    let db = Db::connect().await?;
+    db.check_health().await;
+    println!("replicas healthy:{}", db.healthy_replicas());
+
+    let session = db.session();
+    let todos = Todos(&session);
+    println!("replica find:{:?}", todos.find(TodoId(1)).await?.map(|todo| todo.name));
+    let id = todos.insert("replica example", false).await?;
+    // реплика могла ещё не получить эту строку, сессия читает с primary
+    println!("pinned:{} find:{:?}", session.is_pinned(), todos.find(id).await?.map(|todo| todo.name));
+    todos.delete(id).await?;
     Ok(())
 }
 
@@ -84,14 +199,8 @@ async fn test_listener_cleanup() -> anyhow::Result<()> {
     use sqlx::postgres::PgListener;
     use tokio::time::timeout;
 
-    let pool:sqlx::Pool<sqlx::postgres::Postgres> = sqlx::pool::PoolOptions::<sqlx::postgres::Postgres>::new()
-        .min_connections(1)
-        .max_connections(1)
-        .test_before_acquire(true)
-        .connect(&settings::config().expect("Error parse config"))
-        .await?;
-
-    let mut listener = PgListener::connect_with(&pool).await?;
+    // LISTEN на своём прямом соединении, мимо pgbouncer
+    let mut listener = sqlx_example::listener::connect().await?;
     listener.listen("test_channel").await?;
 
 
@@ -127,7 +236,44 @@ async fn test_listener_cleanup() -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn transaction_example(pool: &sqlx::Pool<sqlx::Postgres>) -> Result<(),anyhow::Error>{
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct TodoCreated {
+    id: TodoId,
+    name: String,
+}
+impl sqlx_example::message::Payload for TodoCreated {
+    const TYPE: &'static str = "todo_created";
+    const VERSION: u32 = 1;
+}
+
+async fn outbox_example(pool: &sqlx::Pool<sqlx::Postgres>) -> Result<(),anyhow::Error>{
+    use sqlx_example::outbox::{self, Destination};
+
+    let mut listener = sqlx_example::listener::connect().await?;
+    listener.listen("todo").await?;
+
+    // relay: неотправленные строки outbox -> NOTIFY todo, sent_at = now()
+    let sent = outbox::relay_once(pool, Destination::Notify, 100).await?;
+    println!("outbox sent:{}", sent);
+    if sent > 0 {
+        let notification = tokio::time::timeout(Duration::from_secs(1), listener.recv()).await??;
+        // в NOTIFY только id, тело - из outbox
+        let notification: outbox::Notification = serde_json::from_str(notification.payload())?;
+        let row = outbox::fetch(pool, notification.id).await?;
+        println!("outbox notification:{} {:?}", notification.id, row.map(|row| row.message.0));
+    }
+    Ok(())
+}
+
+// Запросы объявляются один раз, prepare - лениво на каждом соединении (с pgbouncer - Unnamed)
+fn statements() -> Result<Statements,Error> {
+    Ok(Statements::new(Preparation::from_settings()?)
+        .declare("insert_todo",
+            "INSERT INTO todo(name, created_at,checked_date,checked) VALUES ($1, $2, $3, $4) RETURNING id;",
+            &["VARCHAR", "TIMESTAMPTZ", "TIMESTAMPTZ", "BOOL"]))
+}
+
+async fn transaction_example(pool: &sqlx::Pool<sqlx::Postgres>, statements: &Statements) -> Result<(),anyhow::Error>{
     use chrono::{DateTime,Utc,Local};
   
     let mut transaction:sqlx::Transaction<sqlx::Postgres> = pool.begin().await?;
@@ -146,25 +292,17 @@ async fn transaction_example(pool: &sqlx::Pool<sqlx::Postgres>) -> Result<(),any
     println!("rows_affected:{:?}",res.rows_affected());
 
 
-   // подготовка запроса и использование его клона -------------------------------------------------------------------------------------------------------------
-   let stmt:sqlx::postgres::PgStatement =  transaction.prepare_with(
-        "INSERT INTO todo(name, created_at,checked_date,checked) VALUES ($1::VARCHAR, $2::TIMESTAMPTZ, $3::TIMESTAMPTZ, $4::BOOL) RETURNING id;",&[
-        sqlx::postgres::PgTypeInfo::with_name("VARCHAR"),
-        sqlx::postgres::PgTypeInfo::with_name("TIMESTAMPTZ"),
-        sqlx::postgres::PgTypeInfo::with_name("TIMESTAMPTZ"),
-        sqlx::postgres::PgTypeInfo::with_name("BOOL")]).await?;
-
-    let stmt_clone = stmt.clone();
+   // именованный запрос из реестра: prepare на этом соединении при первом использовании (miss), дальше из кэша (hit)
     // --------------------------------------
     // query требует преобразования результатов из PgRow
     {
          use sqlx::Row;// Для метода try_get
-         let row:sqlx::postgres::PgRow = stmt_clone.query().bind("1 bla name").bind(utc).bind(utc).bind(true).fetch_one(&mut transaction).await?;
+         let row:sqlx::postgres::PgRow = statements.query("insert_todo", &mut transaction).await?.bind("1 bla name").bind(utc).bind(utc).bind(true).fetch_one(&mut transaction).await?;
          println!("id:{:?} ",row.try_get::<i32,_>(0)?);
          
     }  
     // --------------------------------------
-    // query_as_with сразу преобразует результат в тип реализующий PgRow
+    // query_as_with сразу преобразует результат в тип реализующий FromRow
     use sqlx::Arguments;// для метода add
     let mut arg = sqlx::postgres::PgArguments::default();
     arg.add("2 bla name");
@@ -173,43 +311,67 @@ async fn transaction_example(pool: &sqlx::Pool<sqlx::Postgres>) -> Result<(),any
     arg.add(true);
 
     {   
-        // Id реализует PgRow
-        let id:Id = stmt_clone.query_as_with::<Id, sqlx::postgres::PgArguments>(arg).fetch_one(&mut transaction).await?;
+        // TodoId реализует FromRow
+        let id:TodoId = statements.query_as_with::<TodoId>("insert_todo", &mut transaction, arg).await?.fetch_one(&mut transaction).await?;
         println!("id:{:?} ",id);
     } 
     
     // --------------------------------------
     {
-       // Id реализует PgRow
-       let id:Id = stmt_clone.query_as::<Id>().bind("3 bla name").bind(utc).bind(utc).bind(true).fetch_one(&mut transaction).await?;
+       // TodoId реализует FromRow
+       use sqlx::FromRow;
+       let row = statements.query("insert_todo", &mut transaction).await?.bind("3 bla name").bind(utc).bind(utc).bind(true).fetch_one(&mut transaction).await?;
+       let id:TodoId = TodoId::from_row(&row)?;
         println!("id:{:?} ",id);
+
+       sqlx_example::outbox::add(&mut transaction, "todo", &TodoCreated{ id, name: "3 bla name".into() }).await?;
     } 
    
 
     transaction.commit().await?;
     // transaction.rollback().await?;
 
+    // SERIALIZABLE с повтором ----------------------------------------------------------------------------------------------------------------------
+    // обе транзакции читают count и вставляют строку - одна получит 40001 на COMMIT и выполнится заново
+    use sqlx_example::transaction::{with_transaction, IsolationLevel};
+    use std::sync::{Arc, atomic::{AtomicU32, Ordering}};
+    let attempts = Arc::new(AtomicU32::new(0));
+    let insert_with_count = |name: &'static str| {
+        let attempts = attempts.clone();
+        with_transaction(pool, IsolationLevel::Serializable, move |tx| {
+            let attempts = attempts.clone();
+            Box::pin(async move {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                let count: i64 = sqlx::query_scalar("SELECT count(*) FROM todo WHERE name LIKE 'serializable%'").fetch_one(&mut *tx).await?;
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                sqlx::query("INSERT INTO todo (name) VALUES ($1)").bind(format!("{} {}", name, count)).execute(&mut *tx).await?;
+                Ok(count)
+            })
+        })
+    };
+    let (a, b) = tokio::join!(insert_with_count("serializable a"), insert_with_count("serializable b"));
+    println!("serializable: a:{:?} b:{:?} attempts:{}", a?, b?, attempts.load(Ordering::SeqCst));
+
     Ok(())
 }
 
 async fn error_example(pool: &sqlx::Pool<sqlx::Postgres>) -> Result<(),anyhow::Error>{
-   use  sqlx::error::DatabaseError;
-
-   let res:std::result::Result<sqlx::postgres::PgRow, sqlx::Error> = sqlx::query("SELECT -").fetch_one(pool).await;
-   match res {
-       Ok(_) =>{},
-       Err(err) =>{
-       
-        match err {
-            sqlx::Error::Database(pg_db_error) => {
-               
-                let err:sqlx::postgres::PgDatabaseError =  *(pg_db_error.into_error().downcast::<sqlx::postgres::PgDatabaseError>().unwrap());
-                println!("Error:{:?}",err.file()); 
-            },
-            _ => {}
-        }
-       }
+   // sqlx::Error -> self::Error по SQLSTATE
+   let res:std::result::Result<sqlx::postgres::PgRow, self::Error> = sqlx::query("SELECT -").fetch_one(pool).await.map_err(self::Error::from);
+   if let Err(err) = &res {
+       println!("Error:{:?}",err.database_error().map(|err| (&err.code, &err.message)));
+   }
+
+   // повторный id в транзакции, которая откатится
+   let mut transaction = pool.begin().await?;
+   let id: i32 = sqlx::query_scalar("INSERT INTO todo (name) VALUES ('duplicate') RETURNING id").fetch_one(&mut transaction).await?;
+   let res = sqlx::query("INSERT INTO todo (id, name) VALUES ($1, 'duplicate')").bind(id).execute(&mut transaction).await;
+   match res.map_err(self::Error::from) {
+       Err(self::Error::UniqueViolation(err)) => println!("UniqueViolation table:{:?} constraint:{:?}",err.table,err.constraint),
+       other => println!("unexpected:{:?}",other),
    }
+   transaction.rollback().await?;
+
     Ok(())
 }
 
@@ -222,6 +384,20 @@ async fn query_example(pool: /*&sqlx::PgConnection */ &sqlx::Pool<sqlx::Postgres
     use sqlx::Row;
      
 
+    // query!/query_as! в sqlx_example::repo: SQL и типы проверены при компиляции (без базы - по sqlx-data.json)
+    use sqlx_example::repo;
+    if let Some(todo) = repo::find(pool, TodoId(1)).await? {
+        println!("repo find:{:?}",todo);
+    }
+    for todo in repo::list_after(pool, TodoId(0), 3).await? {
+        println!("repo list_after:{:?} ,name:{:?}",todo.id,todo.name);
+    }
+    // необязательные условия через query_builder: $n нумеруются сами, колонка сортировки из белого списка
+    let filter = repo::TodoFilter{ name_contains:Some("pet".into()), checked:Some(false), limit:Some(2), ..Default::default() };
+    for todo in repo::search(pool, &filter).await? {
+        println!("repo search:{:?} ,name:{:?}",todo.id,todo.name);
+    }
+
     let mut rows = sqlx::query("SELECT id,name,created_at,checked FROM todo WHERE id = $1::INT4 AND id > $2::INT4")
         .bind(1)
         .bind(0)
@@ -238,8 +414,8 @@ async fn query_example(pool: /*&sqlx::PgConnection */ &sqlx::Pool<sqlx::Postgres
     .bind(1)
     .bind(0)
     .map(|row: sqlx::postgres::PgRow| {
-        let id: i32 = row.try_get("id").map_err(|err:sqlx::Error| self::Error::Internal(err.to_string())).unwrap();
-        let name: &str = row.try_get("name").map_err(|err:sqlx::Error| self::Error::Internal(err.to_string())).unwrap();
+        let id: i32 = row.try_get("id").map_err(self::Error::from).unwrap();
+        let name: &str = row.try_get("name").map_err(self::Error::from).unwrap();
         println!("id:{} ,name:{}",id,name);
     })
     .fetch(pool);
@@ -249,24 +425,23 @@ async fn query_example(pool: /*&sqlx::PgConnection */ &sqlx::Pool<sqlx::Postgres
    // идеоматичный запрос для доменных типов
    // query_as
 
-    let mut user:User = sqlx::query_as::<_, User>("SELECT id,name,created_at,'Two' as variant FROM todo WHERE id = $1::INT4")
-        .bind(Id(1))
+    let user:User = sqlx::query_as::<_, User>("SELECT id,name,created_at,'Two' as variant FROM todo WHERE id = $1::INT4")
+        .bind(TodoId(1))
         .fetch_one(pool).await?;
     println!("user {:?}",user);
 
     let variant:Variant = sqlx::query_as::<_, Variant>("SELECT 'two' as variant FROM todo WHERE id = $1::INT4")
-        .bind(Id(1))
+        .bind(TodoId(1))
         .fetch_one(pool).await?;
      println!("variant {:?}",variant);
 
-     let mut id:Id = sqlx::query_as::<_, Id>("SELECT 1")
+     let id:TodoId = sqlx::query_as::<_, TodoId>("SELECT 1")
         .fetch_one(pool).await?;
      println!("id {:?}",id);
 
 
     // Способ трейтов
     use sqlx::Executor;// trait impl for sqlx::pool::Pool or sqlx::pool::PoolConnection or sqlx::connection::Connection
-    use sqlx::Execute;// trait impl for &str or  sqlx::query::Query
    
     // аргумент &str
     let variant:Variant = pool.fetch_one("SELECT 'two' as variant FROM todo WHERE id = 1").await?.try_get("variant")?;
@@ -275,39 +450,40 @@ async fn query_example(pool: /*&sqlx::PgConnection */ &sqlx::Pool<sqlx::Postgres
     // вариант через задницу
     use sqlx::Statement;
     let variant:Variant = pool.prepare_with("SELECT 'two' as variant FROM todo WHERE id = $1",&[ sqlx::postgres::PgTypeInfo::with_name("INT4")])
-    .await?.query().bind(Id(1)).fetch_one(pool).await?.try_get("variant")?;
+    .await?.query().bind(TodoId(1)).fetch_one(pool).await?.try_get("variant")?;
     println!("variant {:?}",variant);
 
     // аргумент sqlx::query::Query
-    let variant:Variant =  pool.fetch_one(sqlx::query("SELECT 'two' as variant FROM todo WHERE id = $1::INT4").bind(Id(1))).await?.try_get("variant")?;
+    let variant:Variant =  pool.fetch_one(sqlx::query("SELECT 'two' as variant FROM todo WHERE id = $1::INT4").bind(TodoId(1))).await?.try_get("variant")?;
     println!("variant {:?}",variant);
 
  //-----------------------------------------------------------------------------------------------------------------------------------------------------------
     // sqlx::query_as, sqlx::query_as_with, sqlx::query_scalar  Function
     // Сделайте SQL-запрос, который сопоставлен с конкретным типом, используя FromRow.
 
-    let variant:Variant = sqlx::query_as("SELECT 'two' as variant FROM todo WHERE id = $1::INT4").bind(Id(1)).fetch_one(pool).await?;
+    let variant:Variant = sqlx::query_as("SELECT 'two' as variant FROM todo WHERE id = $1::INT4").bind(TodoId(1)).fetch_one(pool).await?;
     println!("variant:{:?} ",variant);
  
 
-    let (todo_id,): (i32,) = sqlx::query_as(
-        "
-            INSERT INTO todo (name)
-            VALUES ($1)
-            RETURNING id
-        ",
-    )
-    .bind("bla").fetch_one(pool).await?;
+    // проверка до запроса: пустое имя или длиннее VARCHAR(255) - Error::Validation, а не ошибка базы
+    let new_user:User = User::builder().name("bla").variant(Variant::One).build()?;
+    if let Err(err) = User::builder().name("   ").build() {
+        println!("{}",err);// Validation: name must not be empty
+    }
+    let todo_id:TodoId = repo::insert(pool, &new_user.name, false).await?;
+    let new_user:User = User::builder().id(todo_id).name(new_user.name).created_at(new_user.created_at).variant(new_user.variant).build()?;
+    println!("new user {:?}",new_user);
  
     // query_as_with
     use sqlx::Arguments;
     let mut arg = sqlx::postgres::PgArguments::default();
-    arg.add(Id(1));// impl Encode,Type
+    arg.add(TodoId(1));// impl Encode,Type
 
-    sqlx::query_as_with::<sqlx::Postgres,Variant, sqlx::postgres::PgArguments>("SELECT 'two' as variant FROM todo WHERE id = $1::INT4", arg  );
+    let variant:Variant = sqlx::query_as_with::<sqlx::Postgres,Variant, sqlx::postgres::PgArguments>("SELECT 'two' as variant FROM todo WHERE id = $1::INT4", arg  ).fetch_one(pool).await?;
+    println!("query_as_with variant:{:?} ",variant);
 
     // sqlx::query_scalar
-    let variant:Variant = sqlx::query_scalar("SELECT 'two' as variant FROM todo WHERE id = $1::INT4").bind(Id(1)).fetch_one(pool).await?;
+    let variant:Variant = sqlx::query_scalar("SELECT 'two' as variant FROM todo WHERE id = $1::INT4").bind(TodoId(1)).fetch_one(pool).await?;
     println!("variant:{:?} ",variant);
 
 
@@ -342,6 +518,22 @@ async fn query_example(pool: /*&sqlx::PgConnection */ &sqlx::Pool<sqlx::Postgres
     println!("count:{} ",row.rows_affected());
    }
 
+   // bulk_upsert - все строки одним INSERT ... SELECT * FROM UNNEST($1::TEXT[], $2::BOOL[])
+   use sqlx_example::bulk::bulk_upsert;
+   let ids: Vec<i32> = bulk_upsert("todo")
+       .column("name", vec![String::from("pups 1"), String::from("pups 2")])
+       .column("checked", vec![false, false])
+       .fetch_ids(pool).await?;
+   println!("bulk_upsert inserted:{:?} ",ids);
+   // те же id - ON CONFLICT (id) DO UPDATE
+   let ids: Vec<i32> = bulk_upsert("todo")
+       .column("id", ids.clone())
+       .column("name", vec![String::from("pups 1 updated"), String::from("pups 2 updated")])
+       .column("checked", vec![true, true])
+       .on_conflict_update(&["id"], &["name", "checked"])
+       .fetch_ids(pool).await?;
+   println!("bulk_upsert updated:{:?} ",ids);
+
     // fetch
     let mut rows = sqlx::query("SELECT 'two' as variant FROM todo WHERE id > $1::INT4").bind(0).fetch(pool);
     while let Some(row) = rows.try_next().await? {
@@ -350,20 +542,20 @@ async fn query_example(pool: /*&sqlx::PgConnection */ &sqlx::Pool<sqlx::Postgres
     }
 
     // fetch_all
-    let mut rows:Vec<sqlx::postgres::PgRow> = sqlx::query("SELECT 'two' as variant FROM todo WHERE id > $1::INT4").bind(0).fetch_all(pool).await?;
+    let rows:Vec<sqlx::postgres::PgRow> = sqlx::query("SELECT 'two' as variant FROM todo WHERE id > $1::INT4").bind(0).fetch_all(pool).await?;
     for row in rows.iter(){
     // row:sqlx::postgres::PgRow
         println!("count:{:?} ",row.try_get::<Variant,_>("variant")?);
     }
  
     // fetch_one
-    let mut row:sqlx::postgres::PgRow = sqlx::query("SELECT 'two' as variant FROM todo WHERE id = $1::INT4").bind(Id(1)).fetch_one(pool).await?;
+    let row:sqlx::postgres::PgRow = sqlx::query("SELECT 'two' as variant FROM todo WHERE id = $1::INT4").bind(TodoId(1)).fetch_one(pool).await?;
     println!("count:{:?} ",row.try_get::<Variant,_>("variant")?);
 
 
    // try_map Сопоставьте каждую строку результата с другим типом.
    let value:Variant = sqlx::query("SELECT 'two' as variant FROM todo WHERE id = $1::INT4")
-   .bind(Id(1))
+   .bind(TodoId(1))
    .try_map(|row: sqlx::postgres::PgRow | row.try_get::<Variant, _>(0))
    .fetch_one(pool)
    .await?;
@@ -387,7 +579,7 @@ async fn query_example(pool: /*&sqlx::PgConnection */ &sqlx::Pool<sqlx::Postgres
     try_get_unchecked
 */
 
-    let mut rows = sqlx::query("SELECT 'two' as variant FROM todo WHERE id = $1::INT4").bind(Id(1)).fetch(pool);
+    let mut rows = sqlx::query("SELECT 'two' as variant FROM todo WHERE id = $1::INT4").bind(TodoId(1)).fetch(pool);
     while let Some(row) = rows.try_next().await? {
         // row:sqlx::postgres::PgRow
         
@@ -406,16 +598,16 @@ async fn query_example(pool: /*&sqlx::PgConnection */ &sqlx::Pool<sqlx::Postgres
     Ok(())
 }
  
-use my_type_safety::{User,Variant,Id};
+use my_type_safety::{User,Variant};
+use sqlx_example::ids::TodoId;
 mod my_type_safety{
-    use sqlx::{Row,FromRow,Database};
+    use sqlx::Row;
     use sqlx::decode::Decode;
-    use sqlx::encode::Encode;
     use sqlx::types::Type;
-    use sqlx::database::HasValueRef;
-    use std::error::Error;
     use chrono::{DateTime,Utc,Local};
-    use core::fmt::{Debug,Display};
+    use core::fmt::Debug;
+    use sqlx_example::SqlEnum;
+    use sqlx_example::ids::TodoId;
  
      // https://docs.rs/sqlx/0.5.9/sqlx/types/trait.Type.html
      // https://docs.rs/sqlx/0.5.9/sqlx/trait.Decode.html
@@ -430,22 +622,12 @@ mod my_type_safety{
     // Encode - нужен для биндинга обьекта в SQL, (возможно реализовать самостоятельно или через derive)
     // #[sqlx(type_name = "mood", rename_all = "lowercase")] - нужен для определения типа данных по имени поля в таблице
 
-    #[derive(Decode,Encode,sqlx::FromRow,Debug,Default)]
-    pub struct Id(pub i32);
-
-    impl sqlx::Type<sqlx::Postgres> for Id {
-        fn type_info() -> sqlx::postgres::PgTypeInfo {
-            sqlx::postgres::PgTypeInfo::with_name("INT4")//типы из sqlx::postgres::PgTypeInfo::INT4
-        }
-        fn compatible(ty: &sqlx::postgres::PgTypeInfo) -> bool {
-            *ty == Self::type_info()
-        }
-    }
+    // Id для каждой таблицы свой (TodoId, JobId, PostId, UserId), см. sqlx_example::ids::typed_id!
 
     #[derive(Debug)]
      pub struct User { 
         pub name: String, 
-        pub id: Id ,
+        pub id: TodoId ,
         pub created_at: DateTime<Local>,
        // #[sqlx(rename = "name")]
         pub variant:Variant
@@ -453,78 +635,67 @@ mod my_type_safety{
      impl std::default::Default for User{
         fn default() -> Self{
             let utc: DateTime<Local> = Utc::now().with_timezone(&Local);
-            User{created_at:utc,variant:Variant::One, ..Default::default()}
+            // ..Default::default() здесь вызывал бы User::default() рекурсивно
+            User{name:String::new(),id:TodoId::default(),created_at:utc,variant:Variant::One}
         }
     }
-    
-     //#[sqlx(type_name = "variant")] или реализовать sqlx::Type::type_info
-     #[derive(Debug,PartialEq,PartialOrd)]
-     pub enum Variant{
-         One,
-         Two
-     }
 
-    impl sqlx::Type<sqlx::Postgres> for Variant {
-        fn type_info() -> sqlx::postgres::PgTypeInfo {
-            // если поле существует в базе данных, тип выведется из указанного имени
-            // sqlx::postgres::PgTypeInfo::with_name("variant")
-            // иначе вернуть тип поля самостоятельно
-            sqlx::postgres::PgTypeInfo::with_name("TEXT")//типы из sqlx::postgres::PgTypeInfo::TEXT
+    impl User{
+        // todo.name VARCHAR(255), длина в символах
+        pub const NAME_MAX_LEN: usize = 255;
+
+        pub fn builder() -> UserBuilder{
+            UserBuilder{user:User::default()}
         }
-        fn compatible(ty: &sqlx::postgres::PgTypeInfo) -> bool {
-            *ty == Self::type_info()
+
+        pub fn validate(&self) -> Result<(),crate::Error>{
+            if self.name.trim().is_empty(){
+                return Err(crate::Error::Validation{field:"name",message:"must not be empty".into()});
+            }
+            if self.name.chars().count() > Self::NAME_MAX_LEN{
+                return Err(crate::Error::Validation{field:"name",message:format!("must be at most {} characters",Self::NAME_MAX_LEN)});
+            }
+            Ok(())
         }
     }
- 
-    // implementation Encode
-    impl<'q, DB: sqlx::Database> Encode<'q, DB> for Variant
-    where
-        &'q str: Encode<'q, DB>,
-    {
-        fn encode_by_ref(
-            &self,
-            buf: &mut <DB as sqlx::database::HasArguments<'q>>::ArgumentBuffer,
-        ) -> sqlx::encode::IsNull {
-            let val = match self {
-                Variant::One => "One",
-                Variant::Two => "Two",
-            };
-            <&str as Encode<'q, DB>>::encode(val, buf)
+
+    // User::builder().name("pet my cat").variant(Variant::Two).build()?
+    #[derive(Debug)]
+    pub struct UserBuilder{
+        user:User
+    }
+
+    impl UserBuilder{
+        pub fn name(mut self, name: impl Into<String>) -> Self{
+            self.user.name = name.into();
+            self
         }
-        fn size_hint(&self) -> usize {
-            let val = match self {
-                Variant::One => "One",
-                Variant::Two => "Two",
-            };
-            <&str as Encode<'q, DB>>::size_hint(&val)
+        pub fn id(mut self, id: TodoId) -> Self{
+            self.user.id = id;
+            self
         }
-    }
-    // implementation Decode
-    impl<'r> Decode<'r, sqlx::postgres::Postgres> for Variant {
-        fn decode(
-            value: sqlx::postgres::PgValueRef<'r>,
-        ) -> Result< Self, Box< dyn Error + 'static + Send + Sync,>, > {
-            let value = <&'r str as Decode< 'r,sqlx::postgres::Postgres, >>::decode(value)?;
-            match value {
-                "One" | "one" => Result::Ok(Variant::One),
-                "Two" | "two" => Result::Ok(Variant::Two),
-                _ => Err({format!("invalid value {} for enum Variant",&value)}.into()),
-            }
+        pub fn created_at(mut self, created_at: DateTime<Local>) -> Self{
+            self.user.created_at = created_at;
+            self
         }
-    }
-
-    // Для возможности преобразовать результат запроса при sqlx::query_as
-    impl<'a, R: sqlx::Row> sqlx::FromRow<'a, R> for Variant
-    where
-        &'a str: sqlx::ColumnIndex<R>, 
-        Variant: Decode<'a, R::Database>,
-        Variant: Type<R::Database>, usize: sqlx::ColumnIndex<R>,  
-    {
-        fn from_row(row: &'a R) -> sqlx::Result<Self> {  
-            let variant:Variant = row.try_get(/*0_usize*/"variant")?; // variant это если есть такое название поля в базе
-            Result::Ok(variant)
+        pub fn variant(mut self, variant: Variant) -> Self{
+            self.user.variant = variant;
+            self
+        }
+        pub fn build(self) -> Result<User,crate::Error>{
+            self.user.validate()?;
+            Ok(self.user)
         }
     }
+    
+     // Type, Encode, Decode, FromRow (из колонки "variant") генерирует derive из sqlx_example_derive:
+     // Encode пишет "One"/"Two", Decode принимает любой регистр ("two", "TWO")
+     #[derive(SqlEnum,Debug,PartialEq,PartialOrd)]
+     #[sql_enum(case_insensitive, column = "variant")]
+     pub enum Variant{
+         One,
+         Two
+     }
 
      // Для типов не реализующих sqlx::decode::Decode следует реализовать самостоятельно
      // Для возможности преобразовать результат запроса при sqlx::query_as
@@ -540,47 +711,16 @@ mod my_type_safety{
         DateTime<Local>: Type<R::Database>,
         Variant: Decode<'a, R::Database>,
         Variant: Type<R::Database>, usize: sqlx::ColumnIndex<R>,
-       // Id: Decode<'a, R::Database>,
-       // Id: Type<R::Database>,
-        Id: Type<<R as Row>::Database>
+        // TodoId реализован только для Postgres, поэтому bound обязателен
+        TodoId: Decode<'a, R::Database>,
+        TodoId: Type<<R as Row>::Database>
     {
         fn from_row(row: &'a R) -> sqlx::Result<Self> {
             let name: String = row.try_get("name")?;
-            let id: Id = row.try_get("id")?;
+            let id: TodoId = row.try_get("id")?;
             let created_at: DateTime<Local> = row.try_get("created_at")?;
             let variant:Variant = row.try_get(/*3_usize*/"variant")?;  // variant это если есть такое название поля в базе
             Result::Ok(User { name, id, created_at,variant })
         }
     }
 }
-
-
-//-----------------------------------------------------------------------------------------------------------------
-#[derive(thiserror::Error, Debug, Clone)]
-pub enum Error {
-    #[error("Bad config: {0}")]
-    BadConfig(String),
-    #[error("Connecting to database: {0}")]
-    ConnectingToDatabase(String),
-    #[error("Internal error: {0}")]
-    Internal(String),
-    #[error("Not found: {0}")]
-    NotFound(String),
-    #[error("Migrating database: {0}")]
-    DatabaseMigration(String),
-}
-
-impl std::convert::From<sqlx::Error> for self::Error {
-    fn from(err: sqlx::Error) -> Self {
-        match err {
-            sqlx::Error::RowNotFound => self::Error::NotFound("row not found".into()),
-            _ => self::Error::Internal(err.to_string()),
-        }
-    }
-}
-
-impl std::convert::From<sqlx::migrate::MigrateError> for self::Error {
-    fn from(err: sqlx::migrate::MigrateError) -> Self {
-        self::Error::DatabaseMigration(err.to_string())
-    }
-}
\ No newline at end of file
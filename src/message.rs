@@ -0,0 +1,227 @@
+// Сообщения в queue.message JSONB:
+//
+//     {"type": "send_email", "version": 1, "payload": {...}}
+//
+// Payload - типизированное содержимое, Registry по (type, version) выбирает, во что декодировать payload.
+//
+//     enum Job { SendEmail(SendEmail), Cleanup(Cleanup) }
+//     let registry = Registry::new().register(Job::SendEmail).register(Job::Cleanup);
+//     let job: Job = registry.decode(&envelope)?;
+//
+// Задачи лежат в queue между деплоями, поэтому старые версии payload поднимаются до текущей
+// цепочкой upcaster'ов v1 -> v2 -> v3, каждый преобразует JSON на одну версию:
+//
+//     Registry::new()
+//         .upcast("send_email", 1, |mut p| { p["cc"] = json!([]); Ok(p) })
+//         .upcast("send_email", 2, |p| ...)
+//         .register(Job::SendEmail) // SendEmail::VERSION == 3
+
+use crate::ids::JobId;
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use std::collections::HashMap;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("unknown message type {kind:?}")]
+    UnknownType { kind: String },
+    #[error("unsupported version {version} of message {kind:?}, supported: {supported:?}")]
+    UnsupportedVersion { kind: String, version: u32, supported: Vec<u32> },
+    #[error("invalid payload of message {kind:?} v{version}: {source}")]
+    InvalidPayload {
+        kind: String,
+        version: u32,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error("cannot upcast message {kind:?} from v{from_version}: {message}")]
+    Upcast { kind: String, from_version: u32, message: String },
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+}
+
+pub trait Payload: Serialize + DeserializeOwned {
+    // значение поля "type"
+    const TYPE: &'static str;
+    // текущая версия схемы payload
+    const VERSION: u32;
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Envelope {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub version: u32,
+    pub payload: serde_json::Value,
+}
+
+impl Envelope {
+    pub fn new<P: Payload>(payload: &P) -> Result<Self, Error> {
+        Ok(Envelope {
+            kind: P::TYPE.to_string(),
+            version: P::VERSION,
+            payload: serde_json::to_value(payload).map_err(|source| Error::InvalidPayload {
+                kind: P::TYPE.to_string(),
+                version: P::VERSION,
+                source,
+            })?,
+        })
+    }
+
+    // Декодировать без реестра, когда тип известен заранее
+    pub fn decode<P: Payload>(&self) -> Result<P, Error> {
+        if self.kind != P::TYPE {
+            return Err(Error::UnknownType { kind: self.kind.clone() });
+        }
+        if self.version != P::VERSION {
+            return Err(Error::UnsupportedVersion { kind: self.kind.clone(), version: self.version, supported: vec![P::VERSION] });
+        }
+        serde_json::from_value(self.payload.clone()).map_err(|source| Error::InvalidPayload {
+            kind: self.kind.clone(),
+            version: self.version,
+            source,
+        })
+    }
+}
+
+type Decoder<M> = Box<dyn Fn(serde_json::Value) -> Result<M, serde_json::Error> + Send + Sync>;
+type Upcaster = Box<dyn Fn(serde_json::Value) -> Result<serde_json::Value, String> + Send + Sync>;
+
+pub struct Registry<M> {
+    decoders: HashMap<(String, u32), Decoder<M>>,
+    // (type, from_version) -> payload версии from_version + 1
+    upcasters: HashMap<(String, u32), Upcaster>,
+}
+
+impl<M> Default for Registry<M> {
+    fn default() -> Self {
+        Registry { decoders: HashMap::new(), upcasters: HashMap::new() }
+    }
+}
+
+impl<M> Registry<M> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // P::TYPE v P::VERSION декодируется в P и заворачивается в M (обычно вариант enum)
+    pub fn register<P, F>(mut self, wrap: F) -> Self
+    where
+        P: Payload,
+        F: Fn(P) -> M + Send + Sync + 'static,
+    {
+        self.decoders.insert(
+            (P::TYPE.to_string(), P::VERSION),
+            Box::new(move |value| serde_json::from_value::<P>(value).map(&wrap)),
+        );
+        self
+    }
+
+    // Преобразование payload `kind` из версии from_version в from_version + 1
+    pub fn upcast<F>(mut self, kind: &str, from_version: u32, upcaster: F) -> Self
+    where
+        F: Fn(serde_json::Value) -> Result<serde_json::Value, String> + Send + Sync + 'static,
+    {
+        self.upcasters.insert((kind.to_string(), from_version), Box::new(upcaster));
+        self
+    }
+
+    pub fn decode(&self, envelope: &Envelope) -> Result<M, Error> {
+        let envelope = self.upcast_envelope(envelope)?;
+        let decoder = match self.decoders.get(&(envelope.kind.clone(), envelope.version)) {
+            Some(decoder) => decoder,
+            None => return Err(self.not_registered(&envelope)),
+        };
+        let Envelope { kind, version, payload } = envelope;
+        decoder(payload).map_err(|source| Error::InvalidPayload { kind, version, source })
+    }
+
+    // Поднять версию, пока для неё нет decoder'а и есть upcaster
+    pub fn upcast_envelope(&self, envelope: &Envelope) -> Result<Envelope, Error> {
+        let mut envelope = envelope.clone();
+        while !self.decoders.contains_key(&(envelope.kind.clone(), envelope.version)) {
+            let upcaster = match self.upcasters.get(&(envelope.kind.clone(), envelope.version)) {
+                Some(upcaster) => upcaster,
+                None => break,
+            };
+            let payload = std::mem::take(&mut envelope.payload);
+            envelope.payload = upcaster(payload).map_err(|message| Error::Upcast {
+                kind: envelope.kind.clone(),
+                from_version: envelope.version,
+                message,
+            })?;
+            envelope.version += 1;
+        }
+        Ok(envelope)
+    }
+
+    fn not_registered(&self, envelope: &Envelope) -> Error {
+        let mut supported: Vec<u32> = self
+            .decoders
+            .keys()
+            .chain(self.upcasters.keys())
+            .filter(|(kind, _)| *kind == envelope.kind)
+            .map(|(_, version)| *version)
+            .collect();
+        if supported.is_empty() {
+            return Error::UnknownType { kind: envelope.kind.clone() };
+        }
+        supported.sort_unstable();
+        supported.dedup();
+        Error::UnsupportedVersion { kind: envelope.kind.clone(), version: envelope.version, supported }
+    }
+}
+
+// Строка таблицы queue
+#[derive(Debug, sqlx::FromRow)]
+pub struct QueuedJob {
+    pub id: JobId,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub scheduled_for: DateTime<Utc>,
+    pub failed_attempts: i32,
+    pub status: i32,
+    pub message: Json<Envelope>,
+}
+
+pub async fn enqueue<'e, E, P>(executor: E, payload: &P, scheduled_for: DateTime<Utc>) -> Result<JobId, Error>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+    P: Payload,
+{
+    enqueue_envelope(executor, &Envelope::new(payload)?, scheduled_for).await
+}
+
+// Для уже собранного сообщения (например из outbox)
+pub async fn enqueue_envelope<'e, E>(executor: E, envelope: &Envelope, scheduled_for: DateTime<Utc>) -> Result<JobId, Error>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let id = JobId::new();
+    let now = Utc::now();
+    sqlx::query(
+        "INSERT INTO queue (id, created_at, updated_at, scheduled_for, failed_attempts, status, message)
+         VALUES ($1, $2, $2, $3, 0, 0, $4)",
+    )
+    .bind(id)
+    .bind(now)
+    .bind(scheduled_for)
+    .bind(Json(envelope))
+    .execute(executor)
+    .await?;
+    Ok(id)
+}
+
+pub async fn load<'e, E>(executor: E, id: JobId) -> Result<QueuedJob, Error>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    Ok(sqlx::query_as::<_, QueuedJob>(
+        "SELECT id, created_at, updated_at, scheduled_for, failed_attempts, status, message FROM queue WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_one(executor)
+    .await?)
+}
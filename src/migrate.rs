@@ -0,0 +1,125 @@
+// Запуск ./migrations как sqlx::migrate::Migrator::run, но файл с заголовком
+//
+//     -- no-transaction
+//
+// выполняется вне транзакции, по одной команде (нужно для CREATE INDEX CONCURRENTLY,
+// который не может выполняться в транзакции, в т.ч. неявной при нескольких командах в одном запросе).
+// Если такой файл упал посередине, версия записывается с success = false и следующий запуск
+// вернёт MigrateError::Dirty - состояние нужно поправить руками.
+// Упавший CREATE INDEX CONCURRENTLY оставляет индекс INVALID, и при повторе IF NOT EXISTS его
+// пропускает. Поэтому после такого файла (и при ошибке, и без неё) проверяется pg_index.indisvalid:
+// невалидный индекс - ошибка с именем индекса, его нужно удалить (DROP INDEX) и запустить снова.
+
+use crate::sql_script;
+use sqlx::migrate::{Migrate, MigrateError, Migration, Migrator};
+use sqlx::{Acquire, Executor};
+use std::collections::HashMap;
+use std::time::Instant;
+
+pub const NO_TRANSACTION: &str = "-- no-transaction";
+
+// Маркер должен стоять в комментариях до первой команды
+pub fn no_transaction(sql: &str) -> bool {
+    sql.lines()
+        .map(str::trim)
+        .take_while(|line| line.is_empty() || line.starts_with("--"))
+        .any(|line| line == NO_TRANSACTION)
+}
+
+pub async fn run<'a, A>(migrator: &Migrator, pool: A) -> Result<(), MigrateError>
+where
+    A: Acquire<'a, Database = sqlx::Postgres>,
+{
+    let mut conn = pool.acquire().await?;
+
+    // lock the database for exclusive access by the migrator
+    conn.lock().await?;
+    conn.ensure_migrations_table().await?;
+
+    if let Some(version) = conn.dirty_version().await? {
+        return Err(MigrateError::Dirty(version));
+    }
+
+    let applied: HashMap<i64, _> = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|m| (m.version, m))
+        .collect();
+    for version in applied.keys() {
+        if !migrator.iter().any(|m| m.version == *version) {
+            return Err(MigrateError::VersionMissing(*version));
+        }
+    }
+
+    for migration in migrator.iter() {
+        if migration.migration_type.is_down_migration() {
+            continue;
+        }
+        match applied.get(&migration.version) {
+            Some(applied) if applied.checksum != migration.checksum => {
+                return Err(MigrateError::VersionMismatch(migration.version));
+            }
+            Some(_) => {}
+            None if no_transaction(&migration.sql) => apply_without_transaction(&mut conn, migration).await?,
+            None => {
+                conn.apply(migration).await?;
+            }
+        }
+    }
+
+    conn.unlock().await?;
+    Ok(())
+}
+
+async fn apply_without_transaction(conn: &mut sqlx::PgConnection, migration: &Migration) -> Result<(), MigrateError> {
+    let start = Instant::now();
+
+    let mut result = Ok(());
+    for statement in sql_script::split(&migration.sql) {
+        // &str - простой протокол, одна команда в запросе
+        if let Err(err) = conn.execute(statement.sql.as_str()).await {
+            result = Err(err);
+            break;
+        }
+    }
+    let invalid = invalid_indexes(&mut *conn).await?;
+    if !invalid.is_empty() {
+        let message = format!(
+            "migration {} left invalid index(es) {}: drop them with DROP INDEX and run migrations again{}",
+            migration.version,
+            invalid.join(", "),
+            result.as_ref().err().map(|err| format!(" (failed with: {})", err)).unwrap_or_default()
+        );
+        result = Err(sqlx::Error::Protocol(message));
+    }
+
+    // те же поля, что пишет Migrate::apply
+    sqlx::query(
+        "INSERT INTO _sqlx_migrations ( version, description, success, checksum, execution_time )
+         VALUES ( $1, $2, $3, $4, $5 )",
+    )
+    .bind(migration.version)
+    .bind(&*migration.description)
+    .bind(result.is_ok())
+    .bind(&*migration.checksum)
+    .bind(start.elapsed().as_nanos() as i64)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(result?)
+}
+
+// Индексы, которые CREATE INDEX CONCURRENTLY не достроил
+async fn invalid_indexes(conn: &mut sqlx::PgConnection) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT format('%I.%I', n.nspname, c.relname)
+         FROM pg_index i
+         JOIN pg_class c ON c.oid = i.indexrelid
+         JOIN pg_namespace n ON n.oid = c.relnamespace
+         WHERE NOT i.indisvalid AND n.nspname NOT IN ('pg_catalog', 'information_schema')
+         ORDER BY 1",
+    )
+    .fetch_all(conn)
+    .await
+}
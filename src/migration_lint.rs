@@ -0,0 +1,277 @@
+// Проверка файлов ./migrations на DDL, которые блокируют таблицу на время выполнения.
+//
+// Отключить правило для команды - комментарий перед ней или внутри неё:
+//     -- lint:allow create-index-not-concurrently
+//     -- lint:allow all
+//
+// server_version - major версия Postgres, на которую катятся миграции: с 11 ADD COLUMN ... DEFAULT
+// с неизменяемым значением таблицу не переписывает, поэтому правило срабатывает только
+// на volatile значения (random(), nextval(), SERIAL, ...).
+
+use crate::migrate;
+use crate::sql_script::{self, Statement};
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+pub const ADD_COLUMN_DEFAULT: &str = "add-column-default";
+pub const CREATE_INDEX_NOT_CONCURRENTLY: &str = "create-index-not-concurrently";
+pub const DROP_COLUMN: &str = "drop-column";
+pub const ALTER_COLUMN_TYPE: &str = "alter-column-type";
+pub const CONCURRENTLY_IN_TRANSACTION: &str = "concurrently-in-transaction";
+
+// без --server-version
+pub const DEFAULT_SERVER_VERSION: u32 = 11;
+
+// функции, значение которых считается для каждой строки - DEFAULT с ними переписывает таблицу
+const VOLATILE_FUNCTIONS: &[&str] =
+    &["RANDOM", "GEN_RANDOM_UUID", "UUID_GENERATE_V1", "UUID_GENERATE_V4", "CLOCK_TIMESTAMP", "TIMEOFDAY", "NEXTVAL", "SETSEED"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub file: PathBuf,
+    pub line: usize,
+    pub rule: &'static str,
+    pub message: &'static str,
+    pub statement: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: [{}] {}\n    {}",
+            self.file.display(),
+            self.line,
+            self.rule,
+            self.message,
+            self.statement
+        )
+    }
+}
+
+// Все *.sql в каталоге, по порядку имён (как у sqlx Migrator)
+pub fn lint_dir(dir: &Path, server_version: u32) -> std::io::Result<Vec<Finding>> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "sql"))
+        .collect();
+    files.sort();
+
+    let mut findings = Vec::new();
+    for file in files {
+        let sql = std::fs::read_to_string(&file)?;
+        findings.extend(lint(&file, &sql, server_version));
+    }
+    Ok(findings)
+}
+
+pub fn lint(file: &Path, sql: &str, server_version: u32) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    // таблицы, созданные в этом же файле: они пустые, блокировка не страшна
+    let mut created: HashSet<String> = HashSet::new();
+    let no_transaction = migrate::no_transaction(sql);
+
+    for statement in sql_script::split(sql) {
+        let tokens = tokenize(&statement.normalized());
+        let words: Vec<&str> = tokens.iter().map(String::as_str).collect();
+
+        if !no_transaction && words.contains(&"CONCURRENTLY") && !allowed(&statement, CONCURRENTLY_IN_TRANSACTION) {
+            findings.push(Finding {
+                file: file.to_path_buf(),
+                line: statement.line,
+                rule: CONCURRENTLY_IN_TRANSACTION,
+                message: "CONCURRENTLY cannot run inside a transaction, add a `-- no-transaction` header to the file",
+                statement: statement.normalized(),
+            });
+        }
+
+        if let Some(table) = created_table(&words) {
+            created.insert(table);
+            continue;
+        }
+
+        for (rule, message) in check(&words, &created, server_version) {
+            if !allowed(&statement, rule) {
+                findings.push(Finding {
+                    file: file.to_path_buf(),
+                    line: statement.line,
+                    rule,
+                    message,
+                    statement: statement.normalized(),
+                });
+            }
+        }
+    }
+    findings
+}
+
+fn check(words: &[&str], created: &HashSet<String>, server_version: u32) -> Vec<(&'static str, &'static str)> {
+    let mut out = Vec::new();
+
+    // CREATE [UNIQUE] INDEX [CONCURRENTLY] ... ON [ONLY] table
+    if words.first() == Some(&"CREATE") {
+        let rest = if words.get(1) == Some(&"UNIQUE") { &words[2..] } else { &words[1..] };
+        if rest.first() == Some(&"INDEX") && rest.get(1) != Some(&"CONCURRENTLY") {
+            let table = rest
+                .iter()
+                .position(|w| *w == "ON")
+                .and_then(|i| rest[i + 1..].iter().find(|w| **w != "ONLY"))
+                .map(|t| identifier(t));
+            if !table.is_some_and(|t| created.contains(&t)) {
+                out.push((
+                    CREATE_INDEX_NOT_CONCURRENTLY,
+                    "CREATE INDEX locks writes to the table for the whole build, use CREATE INDEX CONCURRENTLY in a `-- no-transaction` migration",
+                ));
+            }
+        }
+        return out;
+    }
+
+    // ALTER TABLE [IF EXISTS] [ONLY] table action [, action ...]
+    if words.len() < 3 || words[0] != "ALTER" || words[1] != "TABLE" {
+        return out;
+    }
+    let mut i = 2;
+    while i < words.len() && matches!(words[i], "IF" | "EXISTS" | "ONLY") {
+        i += 1;
+    }
+    let table = words.get(i).map(|t| identifier(t)).unwrap_or_default();
+    if created.contains(&table) {
+        return out;
+    }
+
+    for action in split_actions(&words[(i + 1).min(words.len())..]) {
+        match action {
+            ["ADD", rest @ ..] if adds_column(rest) && rewrites_table(rest, server_version) => {
+                out.push((
+                    ADD_COLUMN_DEFAULT,
+                    "ADD COLUMN with a volatile default (or any DEFAULT before PostgreSQL 11) rewrites the table under an exclusive lock",
+                ));
+            }
+            ["DROP", rest @ ..] if !matches!(rest.first(), Some(&"CONSTRAINT")) => {
+                out.push((
+                    DROP_COLUMN,
+                    "DROP COLUMN breaks running code that still reads the column, remove usages in a previous deploy",
+                ));
+            }
+            ["ALTER", rest @ ..] if rest.contains(&"TYPE") => {
+                out.push((
+                    ALTER_COLUMN_TYPE,
+                    "ALTER COLUMN ... TYPE rewrites the table and its indexes under an exclusive lock",
+                ));
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+// ADD [COLUMN] ..., но не ADD CONSTRAINT / PRIMARY KEY / ...
+fn adds_column(rest: &[&str]) -> bool {
+    !matches!(rest.first(), Some(&"CONSTRAINT") | Some(&"PRIMARY") | Some(&"UNIQUE") | Some(&"CHECK") | Some(&"FOREIGN") | Some(&"EXCLUDE"))
+}
+
+// До 11 - любой DEFAULT, с 11 - только значения, которые считаются для каждой строки
+fn rewrites_table(rest: &[&str], server_version: u32) -> bool {
+    if server_version < 11 && rest.contains(&"DEFAULT") {
+        return true;
+    }
+    rest.iter().enumerate().any(|(i, word)| match *word {
+        "SERIAL" | "SMALLSERIAL" | "BIGSERIAL" | "SERIAL2" | "SERIAL4" | "SERIAL8" | "IDENTITY" | "STORED" => true,
+        function => rest.get(i + 1) == Some(&"(") && VOLATILE_FUNCTIONS.contains(&function.rsplit('.').next().unwrap_or(function)),
+    })
+}
+
+fn created_table(words: &[&str]) -> Option<String> {
+    let mut i = 1;
+    if words.first() != Some(&"CREATE") {
+        return None;
+    }
+    while i < words.len() && matches!(words[i], "TEMP" | "TEMPORARY" | "UNLOGGED") {
+        i += 1;
+    }
+    if words.get(i) != Some(&"TABLE") {
+        return None;
+    }
+    i += 1;
+    while i < words.len() && matches!(words[i], "IF" | "NOT" | "EXISTS") {
+        i += 1;
+    }
+    words.get(i).map(|t| identifier(t))
+}
+
+fn allowed(statement: &Statement, rule: &str) -> bool {
+    statement.comments.iter().any(|comment| {
+        comment
+            .split("lint:allow")
+            .skip(1)
+            .flat_map(|rest| rest.split(|c: char| c.is_whitespace() || c == ',' || c == '*' || c == '/'))
+            .any(|word| word == rule || word == "all")
+    })
+}
+
+// ALTER TABLE t ADD a int, DROP b -> [ADD a int], [DROP b]
+fn split_actions<'a>(words: &'a [&'a str]) -> Vec<&'a [&'a str]> {
+    let mut actions = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, word) in words.iter().enumerate() {
+        match *word {
+            "(" => depth += 1,
+            ")" => depth -= 1,
+            "," if depth == 0 => {
+                actions.push(&words[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    actions.push(&words[start..]);
+    actions
+}
+
+// Слова в верхнем регистре, скобки и запятые - отдельные токены, "идентификаторы" как есть
+fn tokenize(normalized: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let mut chars = normalized.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                word.push(c);
+                for q in chars.by_ref() {
+                    word.push(q);
+                    if q == '"' {
+                        break;
+                    }
+                }
+            }
+            '(' | ')' | ',' => {
+                if !word.is_empty() {
+                    tokens.push(std::mem::take(&mut word));
+                }
+                tokens.push(c.to_string());
+            }
+            c if c.is_whitespace() => {
+                if !word.is_empty() {
+                    tokens.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.extend(c.to_uppercase()),
+        }
+    }
+    if !word.is_empty() {
+        tokens.push(word);
+    }
+    tokens
+}
+
+// QUEUE -> queue, "Queue" -> Queue, public.queue -> queue
+fn identifier(token: &str) -> String {
+    let name = token.rsplit('.').next().unwrap_or(token);
+    match name.strip_prefix('"').and_then(|n| n.strip_suffix('"')) {
+        Some(quoted) => quoted.to_string(),
+        None => name.to_lowercase(),
+    }
+}
@@ -1,36 +1,64 @@
-
 use sqlx::postgres::PgPoolOptions;
-use sqlx_example::settings;
+use sqlx_example::{migrate, migration_lint, settings};
 use std::time::Duration;
 
-
+// cargo run --bin migrations               - применить ./migrations
+// cargo run --bin migrations -- lint [dir] [--server-version 10] - проверить ./migrations на блокирующие DDL (код выхода 1)
 #[tokio::main]
 async fn main() -> Result<(),sqlx::Error> {
+    if std::env::args().nth(1).as_deref() == Some("lint") {
+        lint();
+    }
+
     // Create a connection pool
     //  for MySQL, use sqlx::mysql::MySqlPoolOptions::new()
-    //  for SQLite, use SqlitePoolOptions::new(), SqliteConnection::connect("sqlite::memory:") 
+    //  for SQLite, use SqlitePoolOptions::new(), SqliteConnection::connect("sqlite::memory:")
     //  etc.
 
-    let config:String = settings::config().expect("Error parse config");
+    // напрямую в Postgres: advisory lock миграций держится на сессии, через pgbouncer не работает
+    let options = settings::direct_connect_options().expect("Error parse config");
     let pool = PgPoolOptions::new()
         .max_connections(5)
         .max_lifetime(Duration::from_secs(30 * 60))
-        .connect(&config)
+        .connect_with(options)
         .await?;
-    
+
     // migrate (создастся таблица _sqlx_migrations)
         migrate(&pool).await?;
 
     Ok(())
 }
 
+fn lint() -> ! {
+    let mut dir = "./migrations".to_string();
+    let mut server_version = migration_lint::DEFAULT_SERVER_VERSION;
+    let mut args = std::env::args().skip(2);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--server-version" => server_version = args.next().and_then(|v| v.parse().ok()).expect("--server-version requires a major version, e.g. 10"),
+            _ => dir = arg,
+        }
+    }
+    let findings = migration_lint::lint_dir(std::path::Path::new(&dir), server_version).expect("Error read migrations dir");
+    for finding in &findings {
+        println!("{}\n", finding);
+    }
+    if findings.is_empty() {
+        println!("no locking DDL found in {}", dir);
+        std::process::exit(0);
+    }
+    println!("{} problem(s), opt out with `-- lint:allow <rule>` above the statement", findings.len());
+    std::process::exit(1);
+}
+
 pub async fn migrate(pool: &sqlx::Pool<sqlx::Postgres>) -> Result<(), sqlx::migrate::MigrateError> {
-    // used macro  
+    // used macro
     // sqlx::migrate!("./migrations").run(pool).await
 
     // or
-   
+
+   // m.run(pool) выполняет каждый файл в транзакции, sqlx_example::migrate::run учитывает `-- no-transaction`
    use sqlx::migrate::Migrator;
    let m = Migrator::new(std::path::Path::new("./migrations")).await?;
-   m.run(pool).await
-}
\ No newline at end of file
+   migrate::run(&m, pool).await
+}
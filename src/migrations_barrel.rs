@@ -1,15 +1,18 @@
 use barrel::{Table,types, Migration};
 use barrel::backend::Pg;
 
+use sqlx::Executor;
 use sqlx::postgres::PgPoolOptions;
 use sqlx_example::settings;
 use std::time::Duration;
 
+// cargo run --bin migrations-barrel          - up
+// cargo run --bin migrations-barrel -- down - откат
 #[tokio::main]
 async fn main() -> Result<(),sqlx::Error> {
     // Create a connection pool
     //  for MySQL, use sqlx::mysql::MySqlPoolOptions::new()
-    //  for SQLite, use SqlitePoolOptions::new(), SqliteConnection::connect("sqlite::memory:") 
+    //  for SQLite, use SqlitePoolOptions::new(), SqliteConnection::connect("sqlite::memory:")
     //  etc.
 
     let config:String = settings::config().expect("Error parse config");
@@ -18,46 +21,75 @@ async fn main() -> Result<(),sqlx::Error> {
         .max_lifetime(Duration::from_secs(30 * 60))
         .connect(&config)
         .await?;
-    
-    migrate(&pool).await?;
-     
-    Ok(())
-}
 
-pub async fn migrate(pool: &sqlx::Pool<sqlx::Postgres>) -> Result<(),sqlx::Error> {
-    let mut m = Migration::new();
+    match std::env::args().nth(1).as_deref() {
+        Some("down") => down(&pool).await?,
+        _ => up(&pool).await?,
+    }
 
-    m.create_table("posts", |t| {
-        t.add_column("id", types::primary());
-        t.add_column("post", types::varchar(255));
-        t.add_column("url", types::varchar(255).indexed(true));
-       
-    });
-    
-    let res:sqlx::postgres::PgQueryResult =  sqlx::query(&m.make::<Pg>()).execute(pool).await?;
-    println!("{:?}",res);
+    Ok(())
+}
 
+pub async fn up(pool: &sqlx::Pool<sqlx::Postgres>) -> Result<(),sqlx::Error> {
     let mut m = Migration::new();
-    // A new table is automatically created with an "id" primary key
-    // To disable that call `without_id` on the return of `create_table`
+    // barrel 0.6 не добавляет "id" автоматически
     m.create_table("users", |t: &mut Table| {
-        t.add_column("name", types::varchar(255)); // Default name is "Anonymous"
+        t.add_column("id", types::primary());
+        t.add_column("name", types::varchar(255));
         t.add_column("description", types::text().nullable(true)); // Can be null
         t.add_column("age", types::integer());
-        t.add_column("posts", types::foreign("posts", vec!["id"]));
         t.add_column("owns_plushy_sharks", types::boolean());
+        t.add_column("created_at", types::custom("TIMESTAMP WITH TIME ZONE").nullable(false));
+        t.add_column("updated_at", types::custom("TIMESTAMP WITH TIME ZONE").nullable(false));
+    });
+
+    // пост принадлежит пользователю: posts.user_id -> users.id
+    m.create_table("posts", |t: &mut Table| {
+        t.add_column("id", types::primary());
+        t.add_column("user_id", types::foreign("users", vec!["id"]));
+        t.add_column("post", types::varchar(255));
+        t.add_column("url", types::varchar(255));
+        t.add_column("created_at", types::custom("TIMESTAMP WITH TIME ZONE").nullable(false));
+        t.add_column("updated_at", types::custom("TIMESTAMP WITH TIME ZONE").nullable(false));
+
+        t.add_index("index_posts_on_url", types::index(vec!["url"]).unique(true));
+        t.add_index("index_posts_on_user_id", types::index(vec!["user_id"]));
     });
 
-    // CREATE TABLE "users" (
-    //     "name" VARCHAR(255) NOT NULL, 
-    //     "description" TEXT, 
-    //     "age" INTEGER NOT NULL, 
-    //     "posts" INTEGER REFERENCES 
-    //     "posts"(id,url) NOT NULL, 
-    //     "owns_plushy_sharks" BOOLEAN NOT NULL);
+    // DEFAULT now() barrel не выражает: .default() всегда в кавычках, а 'now()' вычислится один раз
+    // при создании таблицы. Поэтому отдельным ALTER TABLE
+    for table in ["users", "posts"] {
+        m.change_table(table, |t: &mut Table| {
+            t.inject_custom("ALTER COLUMN \"created_at\" SET DEFAULT now()");
+            t.inject_custom("ALTER COLUMN \"updated_at\" SET DEFAULT now()");
+        });
+    }
+
+    // CREATE TABLE "users" ("id" SERIAL PRIMARY KEY NOT NULL, "name" VARCHAR(255) NOT NULL, ... "updated_at" TIMESTAMP WITH TIME ZONE NOT NULL);
+    // CREATE TABLE "posts" ("id" SERIAL PRIMARY KEY NOT NULL, "user_id" INTEGER REFERENCES "users"(id) NOT NULL, ...);
+    // CREATE UNIQUE INDEX "index_posts_on_url" ON "posts" ("url");
+    // CREATE  INDEX "index_posts_on_user_id" ON "posts" ("user_id");
+    // ALTER TABLE "users" ALTER COLUMN "created_at" SET DEFAULT now(), ALTER COLUMN "updated_at" SET DEFAULT now();
+    // ALTER TABLE "posts" ...
     //println!("{}", m.make::<Pg>());
 
-    let res:sqlx::postgres::PgQueryResult = sqlx::query(&m.make::<Pg>()).execute(pool).await?;
+    // несколько команд в одной строке - только простой протокол (&str), sqlx::query подготавливает один statement
+    let mut transaction = pool.begin().await?;
+    let res:sqlx::postgres::PgQueryResult = transaction.execute(m.make::<Pg>().as_str()).await?;
+    transaction.commit().await?;
+    println!("{:?}",res);
+    Ok(())
+}
+
+pub async fn down(pool: &sqlx::Pool<sqlx::Postgres>) -> Result<(),sqlx::Error> {
+    let mut m = Migration::new();
+    // сначала зависимая таблица, индексы удаляются вместе с таблицей
+    m.drop_table_if_exists("posts");
+    m.drop_table_if_exists("users");
+
+    let mut transaction = pool.begin().await?;
+    let res:sqlx::postgres::PgQueryResult = transaction.execute(m.make::<Pg>().as_str()).await?;
+    transaction.commit().await?;
     println!("{:?}",res);
     Ok(())
 }
@@ -0,0 +1,100 @@
+// Rust структуры #[derive(sqlx::FromRow)] по схеме базы, для бинарника codegen.
+// Колонка типа без соответствия в Rust (rust_type) не попадает в структуру, вместо поля -
+// комментарий TODO: FromRow читает только объявленные поля.
+
+use crate::error::Error;
+use crate::schema::Schema;
+
+// Ключевые слова Rust (strict и reserved), для колонок с таким именем поле пишется как r#name
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false", "fn", "for", "if",
+    "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "static", "struct", "trait",
+    "true", "type", "unsafe", "use", "where", "while", "abstract", "become", "box", "do", "final", "macro",
+    "override", "priv", "try", "typeof", "unsized", "virtual", "yield",
+];
+
+// r#self, r#crate, r#super, r#Self недопустимы - поле переименовывается, колонка в #[sqlx(rename)]
+const NOT_RAW: &[&str] = &["self", "Self", "super", "crate"];
+
+pub fn generate(schema: &Schema, tables: &[String]) -> Result<String, Error> {
+    let mut code = String::from(
+        "// @generated by `cargo run --bin codegen`, do not edit by hand\n\
+         #![allow(dead_code, unused_imports)]\n\n\
+         use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};\n",
+    );
+
+    for name in tables {
+        let table = schema
+            .tables
+            .get(name)
+            .ok_or_else(|| Error::NotFound(format!("table `{}` in database", name)))?;
+
+        code.push_str(&format!("\n// table `{}`\n", name));
+        code.push_str("#[derive(Debug, Clone, sqlx::FromRow)]\n");
+        code.push_str(&format!("pub struct {} {{\n", struct_name(name)));
+        for column in &table.columns {
+            let ty = match rust_type(&column.udt_name) {
+                Some(ty) => ty,
+                None => {
+                    code.push_str(&format!("    // TODO unsupported type `{}`: {}\n", column.data_type, column.name));
+                    continue;
+                }
+            };
+            let ty = if column.nullable { format!("Option<{}>", ty) } else { ty.to_string() };
+            let field = field_name(&column.name);
+            if field.trim_start_matches("r#") != column.name {
+                code.push_str(&format!("    #[sqlx(rename = \"{}\")]\n", column.name));
+            }
+            code.push_str(&format!("    pub {}: {},\n", field, ty));
+        }
+        code.push_str("}\n");
+    }
+
+    Ok(code)
+}
+
+// pg_type.typname -> тип Rust (features sqlx: chrono, uuid, json)
+pub fn rust_type(udt_name: &str) -> Option<&'static str> {
+    Some(match udt_name {
+        "bool" => "bool",
+        "int2" => "i16",
+        "int4" => "i32",
+        "int8" => "i64",
+        "float4" => "f32",
+        "float8" => "f64",
+        "text" | "varchar" | "bpchar" | "name" => "String",
+        "bytea" => "Vec<u8>",
+        "timestamptz" => "DateTime<Utc>",
+        "timestamp" => "NaiveDateTime",
+        "date" => "NaiveDate",
+        "uuid" => "sqlx::types::Uuid",
+        "json" | "jsonb" => "serde_json::Value",
+        _ => return None,
+    })
+}
+
+// todo -> Todo, job_queue -> JobQueue, posts -> Posts: единственное число не угадываем
+// (status, address, news, categories)
+pub fn struct_name(table: &str) -> String {
+    table
+        .split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+// type -> r#type, self -> self_ (с #[sqlx(rename = "self")] в generate)
+pub fn field_name(column: &str) -> String {
+    if NOT_RAW.contains(&column) {
+        format!("{}_", column)
+    } else if KEYWORDS.contains(&column) {
+        format!("r#{}", column)
+    } else {
+        column.to_string()
+    }
+}
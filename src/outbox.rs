@@ -0,0 +1,118 @@
+// Transactional outbox: событие пишется в таблицу outbox (migrations/0004_outbox.sql) в той же
+// транзакции, что и изменения данных, поэтому падение между COMMIT и публикацией его не теряет.
+//
+//     let mut tx = pool.begin().await?;
+//     let id: TodoId = sqlx::query_as("INSERT INTO todo ... RETURNING id").fetch_one(&mut tx).await?;
+//     outbox::add(&mut tx, "todo", &TodoCreated { id }).await?;
+//     tx.commit().await?;
+//
+// Relay забирает неотправленные строки (FOR UPDATE SKIP LOCKED - несколько relay не мешают друг другу),
+// публикует их через NOTIFY или в queue и отмечает sent_at в одной транзакции.
+// В NOTIFY уходит только {"id": ...}: payload NOTIFY ограничен 8000 байт, и большое сообщение
+// иначе падало бы на каждой попытке и блокировало очередь. Тело подписчик читает через fetch(id).
+// Доставка at-least-once: подписчик может получить событие повторно (например, если NOTIFY
+// ушёл, а relay упал до следующего шага), поэтому обработка должна быть идемпотентной по id.
+
+use crate::error::Error;
+use crate::message::{self, Envelope, Payload};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::{PgPool, Postgres};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Destination {
+    // pg_notify(topic, {"id": ...})
+    Notify,
+    // строка в queue с тем же message
+    Queue,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct OutboxRow {
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub topic: String,
+    pub message: Json<Envelope>,
+    pub sent_at: Option<DateTime<Utc>>,
+}
+
+// Что уходит в NOTIFY: serde_json::from_str::<Notification>(notification.payload())
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Notification {
+    pub id: i64,
+}
+
+// Записать событие; executor - транзакция с изменениями данных
+pub async fn add<'e, E, P>(executor: E, topic: &str, payload: &P) -> Result<i64, Error>
+where
+    E: sqlx::Executor<'e, Database = Postgres>,
+    P: Payload,
+{
+    let envelope = Envelope::new(payload)?;
+    Ok(sqlx::query_scalar("INSERT INTO outbox (topic, message) VALUES ($1, $2) RETURNING id")
+        .bind(topic)
+        .bind(Json(envelope))
+        .fetch_one(executor)
+        .await?)
+}
+
+// Событие по id из Notification
+pub async fn fetch<'e, E>(executor: E, id: i64) -> Result<Option<OutboxRow>, Error>
+where
+    E: sqlx::Executor<'e, Database = Postgres>,
+{
+    Ok(sqlx::query_as("SELECT id, created_at, topic, message, sent_at FROM outbox WHERE id = $1")
+        .bind(id)
+        .fetch_optional(executor)
+        .await?)
+}
+
+// Опубликовать до limit неотправленных событий по порядку id, вернуть сколько опубликовано
+pub async fn relay_once(pool: &PgPool, destination: Destination, limit: i64) -> Result<usize, Error> {
+    let mut tx = pool.begin().await?;
+    let rows: Vec<OutboxRow> = sqlx::query_as(
+        "SELECT id, created_at, topic, message, sent_at FROM outbox
+         WHERE sent_at IS NULL ORDER BY id LIMIT $1 FOR UPDATE SKIP LOCKED",
+    )
+    .bind(limit)
+    .fetch_all(&mut tx)
+    .await?;
+    if rows.is_empty() {
+        return Ok(0);
+    }
+
+    for row in &rows {
+        match destination {
+            Destination::Notify => {
+                let payload = serde_json::json!({ "id": row.id }).to_string();
+                // NOTIFY внутри транзакции доставляется при COMMIT
+                sqlx::query("SELECT pg_notify($1, $2)").bind(&row.topic).bind(payload).execute(&mut tx).await?;
+            }
+            Destination::Queue => {
+                message::enqueue_envelope(&mut tx, &row.message.0, Utc::now()).await?;
+            }
+        }
+    }
+
+    let ids: Vec<i64> = rows.iter().map(|row| row.id).collect();
+    sqlx::query("UPDATE outbox SET sent_at = now() WHERE id = ANY($1)").bind(&ids).execute(&mut tx).await?;
+    tx.commit().await?;
+    Ok(rows.len())
+}
+
+// Relay в цикле: пачки по limit, пока есть что отправлять, иначе пауза interval.
+// Конфликты и обрыв соединения не останавливают цикл: после обрыва пачка может уйти повторно,
+// доставка и так at-least-once.
+pub async fn run_relay(pool: &PgPool, destination: Destination, limit: i64, interval: Duration) -> Result<(), Error> {
+    loop {
+        match relay_once(pool, destination, limit).await {
+            Ok(sent) if sent as i64 == limit => continue,
+            Ok(_) => {}
+            Err(err) if err.is_retryable() || err.is_connection_lost() => {}
+            Err(err) => return Err(err),
+        }
+        async_std::task::sleep(interval).await;
+    }
+}
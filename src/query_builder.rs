@@ -0,0 +1,227 @@
+// SELECT с необязательными условиями без ручной нумерации $n:
+//
+//     let todos: Vec<Todo> = select(&TODO)
+//         .filter("checked", Op::Eq, false)
+//         .filter_opt("name", Op::ILike, name.map(|name| format!("%{}%", escape_like(&name))))
+//         .order_by(sort_column, Order::Desc)
+//         .limit(20)
+//         .fetch_all(&pool)
+//         .await?;
+//
+// Значения уходят в PgArguments, номер placeholder - порядок вызова. Имена колонок (в том числе
+// пришедшие от пользователя, как sort_column) проверяются по списку Table::columns: неизвестная
+// колонка - Error::Validation при build/fetch_all, в SQL она не попадает.
+
+use crate::bulk::{quote_ident, UnnestArray};
+use crate::error::Error;
+use sqlx::postgres::{PgArguments, PgRow};
+use sqlx::{Arguments, Encode, Executor, FromRow, Postgres, Type};
+
+// Таблица и колонки, которые разрешено упоминать в запросе
+#[derive(Debug)]
+pub struct Table {
+    pub name: &'static str,
+    pub columns: &'static [&'static str],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    // шаблон со своими % и _, см. escape_like
+    Like,
+    ILike,
+}
+
+impl Op {
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            Op::Eq => "=",
+            Op::Ne => "<>",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Like => "LIKE",
+            Op::ILike => "ILIKE",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    Asc,
+    Desc,
+}
+
+// Значение для LIKE без спецсимволов: "50%_off" -> "50\%\_off"
+pub fn escape_like(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+pub fn select(table: &'static Table) -> Select {
+    Select {
+        table,
+        columns: table.columns.iter().map(|column| quote_ident(column)).collect(),
+        predicates: Vec::new(),
+        order_by: Vec::new(),
+        limit: None,
+        offset: None,
+        arguments: PgArguments::default(),
+        placeholders: 0,
+        unknown_column: None,
+    }
+}
+
+pub struct Select {
+    table: &'static Table,
+    columns: Vec<String>,
+    // соединяются через AND
+    predicates: Vec<String>,
+    order_by: Vec<String>,
+    limit: Option<String>,
+    offset: Option<String>,
+    arguments: PgArguments,
+    placeholders: usize,
+    // первая колонка не из Table::columns
+    unknown_column: Option<String>,
+}
+
+impl Select {
+    // по умолчанию - все колонки Table::columns
+    pub fn columns(mut self, columns: &[&str]) -> Self {
+        self.columns = columns.iter().map(|column| self.column(column)).collect();
+        self
+    }
+
+    // column <op> $n
+    pub fn filter<T>(mut self, column: &str, op: Op, value: T) -> Self
+    where
+        T: Encode<'static, Postgres> + Type<Postgres> + Send + 'static,
+    {
+        let predicate = format!("{} {} {}", self.column(column), op.as_sql(), self.bind(value));
+        self.predicates.push(predicate);
+        self
+    }
+
+    // None - условия нет
+    pub fn filter_opt<T>(self, column: &str, op: Op, value: Option<T>) -> Self
+    where
+        T: Encode<'static, Postgres> + Type<Postgres> + Send + 'static,
+    {
+        match value {
+            Some(value) => self.filter(column, op, value),
+            None => self,
+        }
+    }
+
+    // column = ANY($n), values - Vec<i32>, Vec<String>, ...; пустой массив не находит ничего
+    pub fn any<V: UnnestArray>(mut self, column: &str, values: V) -> Self {
+        let predicate = format!("{} = ANY({})", self.column(column), self.bind(values));
+        self.predicates.push(predicate);
+        self
+    }
+
+    pub fn is_null(mut self, column: &str) -> Self {
+        let predicate = format!("{} IS NULL", self.column(column));
+        self.predicates.push(predicate);
+        self
+    }
+
+    pub fn is_not_null(mut self, column: &str) -> Self {
+        let predicate = format!("{} IS NOT NULL", self.column(column));
+        self.predicates.push(predicate);
+        self
+    }
+
+    // несколько вызовов - сортировка по нескольким колонкам в порядке вызова
+    pub fn order_by(mut self, column: &str, order: Order) -> Self {
+        let order = match order {
+            Order::Asc => format!("{} ASC", self.column(column)),
+            Order::Desc => format!("{} DESC", self.column(column)),
+        };
+        self.order_by.push(order);
+        self
+    }
+
+    pub fn limit(mut self, limit: i64) -> Self {
+        self.limit = Some(self.bind(limit));
+        self
+    }
+
+    pub fn offset(mut self, offset: i64) -> Self {
+        self.offset = Some(self.bind(offset));
+        self
+    }
+
+    pub fn sql(&self) -> String {
+        let mut sql = format!("SELECT {} FROM {}", self.columns.join(", "), quote_ident(self.table.name));
+        if !self.predicates.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&self.predicates.join(" AND "));
+        }
+        if !self.order_by.is_empty() {
+            sql.push_str(" ORDER BY ");
+            sql.push_str(&self.order_by.join(", "));
+        }
+        if let Some(limit) = &self.limit {
+            sql.push_str(" LIMIT ");
+            sql.push_str(limit);
+        }
+        if let Some(offset) = &self.offset {
+            sql.push_str(" OFFSET ");
+            sql.push_str(offset);
+        }
+        sql
+    }
+
+    // SQL и аргументы для sqlx::query_with / query_as_with
+    pub fn build(self) -> Result<(String, PgArguments), Error> {
+        if let Some(column) = self.unknown_column {
+            return Err(Error::Validation {
+                field: "column",
+                message: format!("{} is not a column of {}", column, self.table.name),
+            });
+        }
+        let sql = self.sql();
+        Ok((sql, self.arguments))
+    }
+
+    pub async fn fetch_all<'e, T, E>(self, executor: E) -> Result<Vec<T>, Error>
+    where
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+        E: Executor<'e, Database = Postgres>,
+    {
+        let (sql, arguments) = self.build()?;
+        // SQL свой для каждого набора фильтров - в кэш соединения не кладём (с pgbouncer это обязательно)
+        let rows = sqlx::query_with(&sql, arguments).persistent(false).try_map(|row: PgRow| T::from_row(&row));
+        Ok(rows.fetch_all(executor).await?)
+    }
+
+    fn column(&mut self, column: &str) -> String {
+        if !self.table.columns.contains(&column) && self.unknown_column.is_none() {
+            self.unknown_column = Some(column.to_string());
+        }
+        quote_ident(column)
+    }
+
+    fn bind<T>(&mut self, value: T) -> String
+    where
+        T: Encode<'static, Postgres> + Type<Postgres> + Send + 'static,
+    {
+        self.arguments.add(value);
+        self.placeholders += 1;
+        format!("${}", self.placeholders)
+    }
+}
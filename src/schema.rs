@@ -0,0 +1,289 @@
+// Интроспекция схемы через information_schema / pg_catalog и сравнение двух схем.
+// Используется в schema-diff (живая база против временной базы из ./migrations).
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+// служебная таблица sqlx не является частью схемы приложения
+const IGNORED_TABLES: &[&str] = &["_sqlx_migrations"];
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Schema {
+    pub tables: BTreeMap<String, Table>,
+}
+
+impl Schema {
+    // Таблицы не из ./migrations (например users/posts из migrations-barrel): не сравниваются
+    pub fn remove_tables(&mut self, tables: &[String]) {
+        for table in tables {
+            self.tables.remove(table);
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Table {
+    // в порядке ordinal_position
+    pub columns: Vec<Column>,
+    // имя индекса -> pg_get_indexdef
+    pub indexes: BTreeMap<String, String>,
+    // имя ограничения -> pg_get_constraintdef
+    pub constraints: BTreeMap<String, String>,
+}
+
+impl Table {
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub name: String,
+    // format_type(): "character varying(255)", "timestamp with time zone", ...
+    pub data_type: String,
+    // pg_type.typname: "varchar", "timestamptz", "int4", ...
+    pub udt_name: String,
+    pub nullable: bool,
+    pub default: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct ColumnRow {
+    table_name: String,
+    column_name: String,
+    data_type: String,
+    udt_name: String,
+    nullable: bool,
+    column_default: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct DefinitionRow {
+    table_name: String,
+    name: String,
+    definition: String,
+}
+
+// Загрузить схему `public`
+pub async fn introspect<'e, E>(executor: E) -> Result<Schema, sqlx::Error>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres> + Copy,
+{
+    let mut schema = Schema::default();
+
+    let tables: Vec<(String,)> = sqlx::query_as(
+        "SELECT table_name::TEXT FROM information_schema.tables
+         WHERE table_schema = 'public' AND table_type = 'BASE TABLE' AND table_name <> ALL($1)
+         ORDER BY table_name",
+    )
+    .bind(IGNORED_TABLES)
+    .fetch_all(executor)
+    .await?;
+    for (name,) in tables {
+        schema.tables.insert(name, Table::default());
+    }
+
+    let columns: Vec<ColumnRow> = sqlx::query_as(
+        "SELECT c.relname::TEXT AS table_name,
+                a.attname::TEXT AS column_name,
+                format_type(a.atttypid, a.atttypmod) AS data_type,
+                t.typname::TEXT AS udt_name,
+                NOT a.attnotnull AS nullable,
+                pg_get_expr(d.adbin, d.adrelid) AS column_default
+         FROM pg_attribute a
+         JOIN pg_class c ON c.oid = a.attrelid
+         JOIN pg_namespace n ON n.oid = c.relnamespace
+         JOIN pg_type t ON t.oid = a.atttypid
+         LEFT JOIN pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum
+         WHERE n.nspname = 'public' AND c.relkind = 'r' AND a.attnum > 0 AND NOT a.attisdropped
+         ORDER BY c.relname, a.attnum",
+    )
+    .fetch_all(executor)
+    .await?;
+    for row in columns {
+        if let Some(table) = schema.tables.get_mut(&row.table_name) {
+            table.columns.push(Column {
+                name: row.column_name,
+                data_type: row.data_type,
+                udt_name: row.udt_name,
+                nullable: row.nullable,
+                default: row.column_default,
+            });
+        }
+    }
+
+    let indexes: Vec<DefinitionRow> = sqlx::query_as(
+        "SELECT tablename::TEXT AS table_name, indexname::TEXT AS name, indexdef AS definition
+         FROM pg_indexes WHERE schemaname = 'public'",
+    )
+    .fetch_all(executor)
+    .await?;
+    for row in indexes {
+        if let Some(table) = schema.tables.get_mut(&row.table_name) {
+            table.indexes.insert(row.name, row.definition);
+        }
+    }
+
+    let constraints: Vec<DefinitionRow> = sqlx::query_as(
+        "SELECT c.relname::TEXT AS table_name, con.conname::TEXT AS name,
+                pg_get_constraintdef(con.oid) AS definition
+         FROM pg_constraint con
+         JOIN pg_class c ON c.oid = con.conrelid
+         JOIN pg_namespace n ON n.oid = c.relnamespace
+         WHERE n.nspname = 'public'",
+    )
+    .fetch_all(executor)
+    .await?;
+    for row in constraints {
+        if let Some(table) = schema.tables.get_mut(&row.table_name) {
+            table.constraints.insert(row.name, row.definition);
+        }
+    }
+
+    Ok(schema)
+}
+
+// Расхождение ожидаемой схемы (expected, из миграций) и фактической (actual, живая база)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Difference {
+    MissingTable(String),
+    ExtraTable(String),
+    MissingColumn { table: String, column: String },
+    ExtraColumn { table: String, column: String },
+    ChangedColumn { table: String, column: String, field: &'static str, expected: String, actual: String },
+    MissingIndex { table: String, name: String, definition: String },
+    ExtraIndex { table: String, name: String, definition: String },
+    ChangedIndex { table: String, name: String, expected: String, actual: String },
+    MissingConstraint { table: String, name: String, definition: String },
+    ExtraConstraint { table: String, name: String, definition: String },
+    ChangedConstraint { table: String, name: String, expected: String, actual: String },
+}
+
+// "-" есть в миграциях, но нет в базе; "+" есть в базе, но нет в миграциях; "~" отличается
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Difference::*;
+        match self {
+            MissingTable(table) => write!(f, "- table {}", table),
+            ExtraTable(table) => write!(f, "+ table {}", table),
+            MissingColumn { table, column } => write!(f, "- column {}.{}", table, column),
+            ExtraColumn { table, column } => write!(f, "+ column {}.{}", table, column),
+            ChangedColumn { table, column, field, expected, actual } => write!(
+                f,
+                "~ column {}.{} {}: expected `{}`, found `{}`",
+                table, column, field, expected, actual
+            ),
+            MissingIndex { table, name, definition } => write!(f, "- index {} on {}: {}", name, table, definition),
+            ExtraIndex { table, name, definition } => write!(f, "+ index {} on {}: {}", name, table, definition),
+            ChangedIndex { table, name, expected, actual } => write!(
+                f,
+                "~ index {} on {}: expected `{}`, found `{}`",
+                name, table, expected, actual
+            ),
+            MissingConstraint { table, name, definition } => {
+                write!(f, "- constraint {} on {}: {}", name, table, definition)
+            }
+            ExtraConstraint { table, name, definition } => {
+                write!(f, "+ constraint {} on {}: {}", name, table, definition)
+            }
+            ChangedConstraint { table, name, expected, actual } => write!(
+                f,
+                "~ constraint {} on {}: expected `{}`, found `{}`",
+                name, table, expected, actual
+            ),
+        }
+    }
+}
+
+pub fn diff(expected: &Schema, actual: &Schema) -> Vec<Difference> {
+    let mut out = Vec::new();
+
+    for (name, expected_table) in &expected.tables {
+        match actual.tables.get(name) {
+            None => out.push(Difference::MissingTable(name.clone())),
+            Some(actual_table) => diff_table(name, expected_table, actual_table, &mut out),
+        }
+    }
+    for name in actual.tables.keys() {
+        if !expected.tables.contains_key(name) {
+            out.push(Difference::ExtraTable(name.clone()));
+        }
+    }
+
+    out
+}
+
+fn diff_table(table: &str, expected: &Table, actual: &Table, out: &mut Vec<Difference>) {
+    for column in &expected.columns {
+        let other = match actual.column(&column.name) {
+            Some(other) => other,
+            None => {
+                out.push(Difference::MissingColumn { table: table.into(), column: column.name.clone() });
+                continue;
+            }
+        };
+        let mut changed = |field: &'static str, expected: String, actual: String| {
+            if expected != actual {
+                out.push(Difference::ChangedColumn {
+                    table: table.into(),
+                    column: column.name.clone(),
+                    field,
+                    expected,
+                    actual,
+                });
+            }
+        };
+        changed("type", column.data_type.clone(), other.data_type.clone());
+        changed("nullable", column.nullable.to_string(), other.nullable.to_string());
+        changed(
+            "default",
+            column.default.clone().unwrap_or_else(|| "NULL".into()),
+            other.default.clone().unwrap_or_else(|| "NULL".into()),
+        );
+    }
+    for column in &actual.columns {
+        if expected.column(&column.name).is_none() {
+            out.push(Difference::ExtraColumn { table: table.into(), column: column.name.clone() });
+        }
+    }
+
+    diff_definitions(
+        &expected.indexes,
+        &actual.indexes,
+        |name, definition| Difference::MissingIndex { table: table.into(), name, definition },
+        |name, definition| Difference::ExtraIndex { table: table.into(), name, definition },
+        |name, expected, actual| Difference::ChangedIndex { table: table.into(), name, expected, actual },
+        out,
+    );
+    diff_definitions(
+        &expected.constraints,
+        &actual.constraints,
+        |name, definition| Difference::MissingConstraint { table: table.into(), name, definition },
+        |name, definition| Difference::ExtraConstraint { table: table.into(), name, definition },
+        |name, expected, actual| Difference::ChangedConstraint { table: table.into(), name, expected, actual },
+        out,
+    );
+}
+
+fn diff_definitions(
+    expected: &BTreeMap<String, String>,
+    actual: &BTreeMap<String, String>,
+    missing: impl Fn(String, String) -> Difference,
+    extra: impl Fn(String, String) -> Difference,
+    changed: impl Fn(String, String, String) -> Difference,
+    out: &mut Vec<Difference>,
+) {
+    for (name, definition) in expected {
+        match actual.get(name) {
+            None => out.push(missing(name.clone(), definition.clone())),
+            Some(other) if other != definition => out.push(changed(name.clone(), definition.clone(), other.clone())),
+            Some(_) => {}
+        }
+    }
+    for (name, definition) in actual {
+        if !expected.contains_key(name) {
+            out.push(extra(name.clone(), definition.clone()));
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use sqlx::migrate::{MigrateDatabase, Migrator};
+use sqlx::postgres::PgPoolOptions;
+use sqlx_example::{migrate, schema, settings};
+use std::time::Duration;
+
+// Сравнить схему живой базы (settings) со схемой, построенной из ./migrations во временной базе.
+// Код выхода 1, если есть расхождения.
+// Таблицы, созданные не миграциями из ./migrations (users и posts из migrations-barrel), в схеме
+// из миграций отсутствуют - их нужно исключить явно через --ignore.
+//
+// cargo run --bin schema-diff
+// cargo run --bin schema-diff -- --ignore users --ignore posts
+#[tokio::main]
+async fn main() -> Result<(),anyhow::Error> {
+    let mut ignore: Vec<String> = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ignore" => ignore.push(args.next().ok_or_else(|| anyhow::anyhow!("--ignore requires a table name"))?),
+            _ => anyhow::bail!("unexpected argument {}", arg),
+        }
+    }
+
+    let config:String = settings::config().expect("Error parse config");
+    let live = PgPoolOptions::new()
+        .max_connections(1)
+        .max_lifetime(Duration::from_secs(30 * 60))
+        .connect(&config)
+        .await?;
+
+    // временная база на том же сервере, мимо pgbouncer: её нет в [databases], а миграции берут advisory lock
+    let scratch_name = format!("{}_schema_diff_{}", settings::dbname().expect("Error parse config"), std::process::id());
+    let scratch_config = settings::direct_config_for_dbname(&scratch_name).expect("Error parse config");
+    sqlx::Postgres::create_database(&scratch_config).await?;
+
+    let expected = build_expected(&scratch_config).await;
+    // базу удаляем в любом случае; ошибка миграций важнее ошибки удаления
+    let dropped = sqlx::Postgres::drop_database(&scratch_config).await;
+    let mut expected = expected?;
+    dropped?;
+
+    let mut actual = schema::introspect(&live).await?;
+    live.close().await;
+    expected.remove_tables(&ignore);
+    actual.remove_tables(&ignore);
+
+    let differences = schema::diff(&expected, &actual);
+    if differences.is_empty() {
+        println!("schema is up to date with ./migrations");
+        return Ok(());
+    }
+
+    println!("--- ./migrations\n+++ {}", settings::dbname().expect("Error parse config"));
+    for difference in &differences {
+        println!("{}", difference);
+    }
+    println!("{} difference(s)", differences.len());
+    std::process::exit(1)
+}
+
+// Пул закрывается и при ошибке: открытая сессия не даст удалить временную базу
+async fn build_expected(scratch_config: &str) -> Result<schema::Schema, anyhow::Error> {
+    let m = Migrator::new(std::path::Path::new("./migrations")).await?;
+    let scratch = PgPoolOptions::new()
+        .max_connections(1)
+        .connect(scratch_config)
+        .await?;
+
+    let expected = async {
+        migrate::run(&m, &scratch).await?;
+        Ok(schema::introspect(&scratch).await?)
+    }
+    .await;
+    scratch.close().await;
+    expected
+}
@@ -0,0 +1,117 @@
+// Последовательности SERIAL/IDENTITY колонок после вставки с явными id (COPY, восстановление дампа)
+// отстают от данных, и следующий обычный INSERT падает с duplicate key. resync поднимает
+// последовательность до max(колонки); если она уже впереди - не трогает.
+// Подъём - один SELECT setval(GREATEST(max, last_value)): nextval другой сессии между проверкой
+// и setval не отодвигает последовательность назад.
+
+use crate::bulk::quote_ident;
+use crate::error::Error;
+use sqlx::postgres::PgConnection;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequenceFix {
+    // schema.table
+    pub table: String,
+    pub column: String,
+    pub sequence: String,
+    // до исправления, None - nextval ещё не вызывался
+    pub last_value: Option<i64>,
+    // max(column), None - таблица пустая
+    pub max: Option<i64>,
+    // MINVALUE последовательности - первое значение nextval
+    pub min_value: i64,
+}
+
+impl SequenceFix {
+    // последовательность выдаст id, который уже есть в таблице
+    pub fn is_behind(&self) -> bool {
+        match (self.max, self.last_value) {
+            (Some(max), Some(last_value)) => last_value < max,
+            (Some(max), None) => max >= self.min_value,
+            (None, _) => false,
+        }
+    }
+}
+
+// Последовательность колонки (SERIAL или GENERATED ... AS IDENTITY), None - колонка без неё
+pub async fn sequence_of(conn: &mut PgConnection, table: &str, column: &str) -> Result<Option<String>, Error> {
+    // таблица - имя с возможной схемой (поэтому в кавычках), колонка - как есть
+    Ok(sqlx::query_scalar("SELECT pg_get_serial_sequence($1, $2)")
+        .bind(quote_ident(table))
+        .bind(column)
+        .fetch_one(conn)
+        .await?)
+}
+
+// Поднять последовательность колонки до max(column), None - у колонки нет последовательности
+pub async fn resync(conn: &mut PgConnection, table: &str, column: &str) -> Result<Option<SequenceFix>, Error> {
+    let sequence = match sequence_of(&mut *conn, table, column).await? {
+        Some(sequence) => sequence,
+        None => return Ok(None),
+    };
+    let fix = inspect(&mut *conn, quote_ident(table), column.to_string(), sequence).await?;
+    if fix.is_behind() {
+        apply(conn, &fix).await?;
+    }
+    Ok(Some(fix))
+}
+
+// Все SERIAL/IDENTITY колонки пользовательских таблиц; с dry_run только проверить
+pub async fn resync_all(conn: &mut PgConnection, dry_run: bool) -> Result<Vec<SequenceFix>, Error> {
+    let columns: Vec<(String, String, String)> = sqlx::query_as(
+        "SELECT format('%I.%I', n.nspname, c.relname), a.attname::TEXT, s.sequence
+         FROM pg_attribute a
+         JOIN pg_class c ON c.oid = a.attrelid
+         JOIN pg_namespace n ON n.oid = c.relnamespace
+         CROSS JOIN LATERAL (SELECT pg_get_serial_sequence(format('%I.%I', n.nspname, c.relname), a.attname) AS sequence) s
+         WHERE c.relkind IN ('r', 'p')
+           AND a.attnum > 0 AND NOT a.attisdropped
+           AND n.nspname NOT IN ('pg_catalog', 'information_schema')
+           AND n.nspname NOT LIKE 'pg\\_%'
+           AND s.sequence IS NOT NULL
+         ORDER BY 1, 2",
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut fixes = Vec::with_capacity(columns.len());
+    for (table, column, sequence) in columns {
+        let fix = inspect(&mut *conn, table, column, sequence).await?;
+        if fix.is_behind() && !dry_run {
+            apply(&mut *conn, &fix).await?;
+        }
+        fixes.push(fix);
+    }
+    Ok(fixes)
+}
+
+// table - уже в кавычках
+async fn inspect(conn: &mut PgConnection, table: String, column: String, sequence: String) -> Result<SequenceFix, Error> {
+    let (last_value, max, min_value): (Option<i64>, Option<i64>, i64) = sqlx::query_as(&format!(
+        "SELECT pg_sequence_last_value($1::regclass), (SELECT max({})::INT8 FROM {}),
+                (SELECT seqmin FROM pg_sequence WHERE seqrelid = $1::regclass)",
+        quote_ident(&column),
+        table
+    ))
+    .bind(&sequence)
+    .fetch_one(conn)
+    .await?;
+    Ok(SequenceFix { table, column, sequence, last_value, max, min_value })
+}
+
+// max и last_value читаются заново в том же запросе, что и setval. Следующий nextval вернёт
+// большее из них + 1, но не меньше MINVALUE (setval ниже MINVALUE - ошибка)
+async fn apply(conn: &mut PgConnection, fix: &SequenceFix) -> Result<(), Error> {
+    sqlx::query(&format!(
+        "SELECT setval($1::regclass, GREATEST(m.value, s.seqmin), m.value >= s.seqmin)
+         FROM (SELECT GREATEST((SELECT max({})::INT8 FROM {}), pg_sequence_last_value($1::regclass)) AS value) m
+         JOIN pg_sequence s ON s.seqrelid = $1::regclass
+         WHERE m.value IS NOT NULL",
+        quote_ident(&fix.column),
+        fix.table
+    ))
+    .bind(&fix.sequence)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
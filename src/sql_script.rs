@@ -0,0 +1,204 @@
+// Разбор SQL файла миграции на отдельные команды.
+// Учитывает строки '...', идентификаторы "...", $tag$...$tag$, комментарии -- и /* */ (вложенные).
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+    // текст команды без завершающей `;`, с комментариями
+    pub sql: String,
+    // номер строки (с 1), на которой начинается команда
+    pub line: usize,
+    // тексты комментариев внутри команды и перед ней (после предыдущей `;`)
+    pub comments: Vec<String>,
+}
+
+impl Statement {
+    // Текст без комментариев, строковые литералы заменены на '', пробелы схлопнуты
+    pub fn normalized(&self) -> String {
+        let mut out = String::new();
+        scan(&self.sql, |event| match event {
+            Event::Code(c) => {
+                if c.is_whitespace() {
+                    if !out.ends_with(' ') && !out.is_empty() {
+                        out.push(' ');
+                    }
+                } else {
+                    out.push(c);
+                }
+            }
+            Event::Literal(text) => out.push_str(if text.starts_with('"') { text } else { "''" }),
+            Event::Comment(_) => {
+                if !out.ends_with(' ') && !out.is_empty() {
+                    out.push(' ');
+                }
+            }
+            Event::End => {}
+        });
+        out.trim().to_string()
+    }
+}
+
+pub fn split(sql: &str) -> Vec<Statement> {
+    let mut statements = Vec::new();
+    let mut current = Statement { sql: String::new(), line: 0, comments: Vec::new() };
+    let mut line = 1;
+
+    scan(sql, |event| {
+        match event {
+            Event::Code(c) => {
+                if current.line == 0 && !c.is_whitespace() {
+                    current.line = line;
+                }
+                current.sql.push(c);
+            }
+            Event::Literal(text) => {
+                if current.line == 0 {
+                    current.line = line;
+                }
+                current.sql.push_str(text);
+            }
+            Event::Comment(text) => {
+                current.comments.push(text.to_string());
+                current.sql.push_str(text);
+            }
+            Event::End => {
+                let done = std::mem::replace(&mut current, Statement { sql: String::new(), line: 0, comments: Vec::new() });
+                if done.line != 0 {
+                    statements.push(Statement { sql: done.sql.trim().to_string(), ..done });
+                } else {
+                    // только комментарии - относятся к следующей команде
+                    current.comments = done.comments;
+                }
+            }
+        }
+        if let Event::Code('\n') = event {
+            line += 1;
+        }
+        if let Event::Literal(text) | Event::Comment(text) = event {
+            line += text.matches('\n').count();
+        }
+    });
+
+    if current.line != 0 {
+        current.sql = current.sql.trim().to_string();
+        statements.push(current);
+    }
+    statements
+}
+
+#[derive(Clone, Copy)]
+enum Event<'a> {
+    // символ вне литералов и комментариев (кроме `;`)
+    Code(char),
+    // строка, "идентификатор" или $tag$тело$tag$ целиком
+    Literal(&'a str),
+    Comment(&'a str),
+    // `;` вне литералов
+    End,
+}
+
+fn scan<'a>(sql: &'a str, mut f: impl FnMut(Event<'a>)) {
+    let bytes = sql.as_bytes();
+    let mut i = 0;
+    while i < sql.len() {
+        let rest = &sql[i..];
+        let c = rest.chars().next().unwrap();
+        let len = if rest.starts_with("--") {
+            rest.find('\n').unwrap_or(rest.len())
+        } else if rest.starts_with("/*") {
+            block_comment_len(rest)
+        } else if (c == 'E' || c == 'e') && rest[1..].starts_with('\'') && !prev_is_word(sql, i) {
+            1 + escaped_len(&rest[1..])
+        } else if c == '\'' || c == '"' {
+            quoted_len(rest, bytes[i])
+        } else if c == '$' {
+            dollar_quote_len(rest).unwrap_or(0)
+        } else {
+            0
+        };
+
+        if len > 0 {
+            let text = &rest[..len];
+            if text.starts_with("--") || text.starts_with("/*") {
+                f(Event::Comment(text));
+            } else {
+                f(Event::Literal(text));
+            }
+            i += len;
+        } else {
+            f(if c == ';' { Event::End } else { Event::Code(c) });
+            i += c.len_utf8();
+        }
+    }
+}
+
+fn block_comment_len(rest: &str) -> usize {
+    let mut depth = 0;
+    let mut i = 0;
+    while i < rest.len() {
+        if rest[i..].starts_with("/*") {
+            depth += 1;
+            i += 2;
+        } else if rest[i..].starts_with("*/") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += rest[i..].chars().next().unwrap().len_utf8();
+        }
+    }
+    rest.len()
+}
+
+// 'it''s' / "a""b" - кавычка экранируется удвоением
+fn quoted_len(rest: &str, quote: u8) -> usize {
+    let bytes = rest.as_bytes();
+    let mut i = 1;
+    while i < bytes.len() {
+        if bytes[i] == quote {
+            if bytes.get(i + 1) == Some(&quote) {
+                i += 2;
+                continue;
+            }
+            return i + 1;
+        }
+        i += 1;
+    }
+    rest.len()
+}
+
+// E'it\'s' - экранирование обратной косой чертой
+fn escaped_len(rest: &str) -> usize {
+    let bytes = rest.as_bytes();
+    let mut i = 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'\'' if bytes.get(i + 1) == Some(&b'\'') => i += 2,
+            b'\'' => return i + 1,
+            _ => i += 1,
+        }
+    }
+    rest.len()
+}
+
+fn prev_is_word(sql: &str, i: usize) -> bool {
+    sql[..i].chars().next_back().is_some_and(|c| c.is_alphanumeric() || c == '_')
+}
+
+// $$...$$ или $tag$...$tag$; `$1` - параметр, не кавычка
+fn dollar_quote_len(rest: &str) -> Option<usize> {
+    let close = rest[1..].find('$')? + 1;
+    let tag = &rest[..=close];
+    if !tag[1..tag.len() - 1].chars().all(|c| c.is_alphanumeric() || c == '_')
+        || tag[1..].starts_with(|c: char| c.is_ascii_digit())
+    {
+        return None;
+    }
+    let body = &rest[tag.len()..];
+    Some(match body.find(tag) {
+        Some(end) => tag.len() + end + tag.len(),
+        None => rest.len(),
+    })
+}
@@ -0,0 +1,332 @@
+// Импорт CSV через временную staging таблицу: одна плохая строка больше не роняет весь COPY.
+//
+//     let report = StagingImport::new("todo", vec![
+//             Column::new("id", "INT4"),
+//             Column::new("name", "VARCHAR").required().max_length(255),
+//             Column::new("checked", "BOOLEAN"),
+//         ])
+//         .key(&["id"])
+//         .on_conflict(OnConflict::Update)
+//         .run(&mut conn, "todo.csv", Some(Path::new("todo.rejected.csv")))
+//         .await?;
+//
+// В одной транзакции:
+// 1. COPY файла в TEMP таблицу из TEXT колонок (+ line - номер строки данных, без заголовка), типы не проверяются;
+// 2. проверка каждой строки: обязательные, приводимость к типу колонки, длина, повторы key в файле,
+//    для OnConflict::Reject - key, который уже есть в таблице; причина пишется в error;
+// 3. остальные - INSERT ... SELECT в таблицу, затем sequences::resync для колонок;
+// 4. строки с error - в report (CSV: line, error, исходные значения). Файл пишется только после
+//    удачной вставки и удаляется, если не прошёл COMMIT, - report без импорта не остаётся.
+// Пустое значение без кавычек - NULL (вставляется NULL, а не DEFAULT колонки).
+// Остальные ограничения таблицы (NOT NULL неимпортируемых колонок, FOREIGN KEY, CHECK) проверяет
+// уже INSERT, и их нарушение откатывает весь импорт.
+// Файл с неверным числом колонок в строке по-прежнему прерывает COPY - это ошибка формата, а не данных.
+
+use crate::bulk::quote_ident;
+use crate::copy_out::{self, Source};
+use crate::error::Error;
+use crate::sequences;
+use sqlx::postgres::PgConnection;
+use sqlx::{Acquire, Executor};
+use std::io::Write;
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+pub struct Column {
+    pub name: String,
+    // тип колонки таблицы: INT4, BOOLEAN, TIMESTAMPTZ, NUMERIC(10, 2), ...
+    // попадает в SQL как есть, поэтому run проверяет допустимые символы (valid_type)
+    pub sql_type: String,
+    pub required: bool,
+    pub max_length: Option<usize>,
+}
+
+impl Column {
+    pub fn new(name: &str, sql_type: &str) -> Self {
+        Column { name: name.to_string(), sql_type: sql_type.to_string(), required: false, max_length: None }
+    }
+
+    // не NULL и не пустая строка
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    // в символах, как VARCHAR(n) (явное приведение к VARCHAR(n) молча обрезает строку)
+    pub fn max_length(mut self, max_length: usize) -> Self {
+        self.max_length = Some(max_length);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnConflict {
+    // строка с key, который уже есть в таблице, попадает в report
+    Reject,
+    // ON CONFLICT DO NOTHING
+    Skip,
+    // ON CONFLICT (key) DO UPDATE SET остальные колонки
+    Update,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ImportReport {
+    // строк в файле без заголовка
+    pub total: u64,
+    // вставлено или обновлено
+    pub imported: u64,
+    pub rejected: u64,
+}
+
+pub struct StagingImport {
+    table: String,
+    columns: Vec<Column>,
+    key: Vec<String>,
+    on_conflict: OnConflict,
+    header: bool,
+}
+
+const STAGING: &str = "import_staging";
+
+// Ошибки приведения значения к типу - значит, плохие данные. Остальные (нехватка памяти,
+// неизвестный тип) прерывают импорт, а не превращают каждую строку в "not a valid"
+const CAST_ERRORS: &[&str] = &[
+    "invalid_text_representation",
+    "invalid_datetime_format",
+    "datetime_field_overflow",
+    "numeric_value_out_of_range",
+    "string_data_right_truncation",
+];
+
+// Имя типа с модификаторами и массивами: INT4, VARCHAR(255), NUMERIC(10, 2), TEXT[], double precision
+fn valid_type(sql_type: &str) -> bool {
+    !sql_type.trim().is_empty()
+        && sql_type.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | ' ' | '(' | ')' | ',' | '[' | ']'))
+}
+
+impl StagingImport {
+    // columns - в порядке колонок CSV файла
+    pub fn new(table: &str, columns: Vec<Column>) -> Self {
+        StagingImport { table: table.to_string(), columns, key: Vec::new(), on_conflict: OnConflict::Reject, header: true }
+    }
+
+    // уникальные колонки: повтор в файле - отказ, совпадение с таблицей - по on_conflict
+    pub fn key(mut self, key: &[&str]) -> Self {
+        self.key = key.iter().map(|column| column.to_string()).collect();
+        self
+    }
+
+    pub fn on_conflict(mut self, on_conflict: OnConflict) -> Self {
+        self.on_conflict = on_conflict;
+        self
+    }
+
+    // первая строка файла - заголовок (по умолчанию да)
+    pub fn header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+
+    pub async fn run(&self, conn: &mut PgConnection, csv: impl AsRef<Path>, report: Option<&Path>) -> Result<ImportReport, Error> {
+        if let Some(column) = self.key.iter().find(|key| !self.columns.iter().any(|column| &column.name == *key)) {
+            return Err(Error::Validation { field: "key", message: format!("{} is not an imported column", column) });
+        }
+        if let Some(column) = self.columns.iter().find(|column| !valid_type(&column.sql_type)) {
+            return Err(Error::Validation {
+                field: "sql_type",
+                message: format!("{:?} of column {} is not a type name", column.sql_type, column.name),
+            });
+        }
+
+        let mut tx = conn.begin().await?;
+        tx.execute(self.create_staging().as_str()).await?;
+
+        let file = async_std::fs::File::open(csv.as_ref()).await?;
+        let mut copy = tx.copy_in_raw(&self.copy_statement()).await?;
+        copy.read_from(file).await?;
+        let total = copy.finish().await?;
+
+        tx.execute(self.validate().as_str()).await?;
+        if !self.key.is_empty() {
+            tx.execute(self.reject_duplicates().as_str()).await?;
+            if self.on_conflict == OnConflict::Reject {
+                tx.execute(self.reject_existing().as_str()).await?;
+            }
+        }
+
+        let rejected: i64 = sqlx::query_scalar(&format!("SELECT count(*) FROM {} WHERE error IS NOT NULL", STAGING))
+            .fetch_one(&mut tx)
+            .await?;
+
+        let imported = tx.execute(self.merge().as_str()).await?.rows_affected();
+        // до COMMIT: обычный INSERT после импорта не получит id, который только что вставлен явно
+        for column in &self.columns {
+            sequences::resync(&mut tx, &self.table, &column.name).await?;
+        }
+
+        match report {
+            Some(path) => {
+                if let Err(err) = self.write_report(&mut tx, path).await {
+                    let _ = std::fs::remove_file(path);
+                    return Err(err);
+                }
+                if let Err(err) = tx.commit().await {
+                    let _ = std::fs::remove_file(path);
+                    return Err(err.into());
+                }
+            }
+            None => tx.commit().await?,
+        }
+        Ok(ImportReport { total, imported, rejected: rejected as u64 })
+    }
+
+    async fn write_report(&self, conn: &mut PgConnection, path: &Path) -> Result<(), Error> {
+        let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+        copy_out::export(conn, &Source::Query(self.report_query()), copy_out::Format::Csv, &mut out).await?;
+        out.flush()?;
+        Ok(())
+    }
+
+    fn staging_column(&self, index: usize) -> String {
+        quote_ident(&self.columns[index].name)
+    }
+
+    // pg_temp.import_cast_N(text) - значение или NULL, если не приводится к типу колонки N
+    fn cast(&self, index: usize) -> String {
+        self.qualified_cast("", index)
+    }
+
+    // alias - "s." там, где в запросе есть таблица с колонками тех же имён
+    fn qualified_cast(&self, alias: &str, index: usize) -> String {
+        format!("pg_temp.import_cast_{}({}{})", index, alias, self.staging_column(index))
+    }
+
+    fn create_staging(&self) -> String {
+        let mut sql = format!(
+            "CREATE TEMP TABLE {} (line BIGSERIAL, error TEXT, {}) ON COMMIT DROP;\n",
+            STAGING,
+            (0..self.columns.len()).map(|i| format!("{} TEXT", self.staging_column(i))).collect::<Vec<_>>().join(", ")
+        );
+        for (i, column) in self.columns.iter().enumerate() {
+            sql.push_str(&format!(
+                // функции pg_temp живут до конца сессии, тип мог измениться с прошлого импорта
+                "DROP FUNCTION IF EXISTS pg_temp.import_cast_{0}(TEXT);
+                 CREATE FUNCTION pg_temp.import_cast_{0}(value TEXT) RETURNS {1} LANGUAGE plpgsql AS $$
+                 BEGIN RETURN value::{1};
+                 EXCEPTION WHEN {2} THEN RETURN NULL; END $$;\n",
+                i,
+                column.sql_type,
+                CAST_ERRORS.join(" OR ")
+            ));
+        }
+        sql
+    }
+
+    fn copy_statement(&self) -> String {
+        format!(
+            "COPY {} ({}) FROM STDIN WITH (FORMAT csv, HEADER {})",
+            STAGING,
+            (0..self.columns.len()).map(|i| self.staging_column(i)).collect::<Vec<_>>().join(", "),
+            self.header
+        )
+    }
+
+    fn validate(&self) -> String {
+        let mut checks = Vec::new();
+        for (i, column) in self.columns.iter().enumerate() {
+            let value = self.staging_column(i);
+            let name = column.name.replace('\'', "''");
+            // NULL в key не сравнить ни с файлом, ни с таблицей
+            if column.required || self.key.contains(&column.name) {
+                checks.push(format!("CASE WHEN NULLIF(btrim({}), '') IS NULL THEN '{}: required' END", value, name));
+            }
+            checks.push(format!(
+                "CASE WHEN {0} IS NOT NULL AND {1} IS NULL THEN '{2}: not a valid {3}' END",
+                value,
+                self.cast(i),
+                name,
+                column.sql_type.replace('\'', "''")
+            ));
+            if let Some(max_length) = column.max_length {
+                checks.push(format!(
+                    "CASE WHEN char_length({0}) > {1} THEN '{2}: longer than {1} characters' END",
+                    value, max_length, name
+                ));
+            }
+        }
+        format!("UPDATE {} SET error = NULLIF(concat_ws('; ', {}), '')", STAGING, checks.join(", "))
+    }
+
+    fn key_casts(&self, alias: &str) -> Vec<String> {
+        self.key
+            .iter()
+            .map(|key| self.qualified_cast(alias, self.columns.iter().position(|column| &column.name == key).unwrap()))
+            .collect()
+    }
+
+    // повтор key в файле - в таблицу идёт первая строка
+    fn reject_duplicates(&self) -> String {
+        format!(
+            "UPDATE {0} s SET error = 'duplicate of line ' || d.first FROM (
+                 SELECT line, first_value(line) OVER (PARTITION BY {1} ORDER BY line) AS first FROM {0} WHERE error IS NULL
+             ) d WHERE s.line = d.line AND d.first <> d.line",
+            STAGING,
+            self.key_casts("").join(", ")
+        )
+    }
+
+    fn reject_existing(&self) -> String {
+        let condition = self
+            .key
+            .iter()
+            .zip(self.key_casts("s."))
+            .map(|(key, cast)| format!("t.{} = {}", quote_ident(key), cast))
+            .collect::<Vec<_>>()
+            .join(" AND ");
+        format!(
+            "UPDATE {0} s SET error = 'already exists in {1}' WHERE s.error IS NULL AND EXISTS (SELECT 1 FROM {2} t WHERE {3})",
+            STAGING,
+            self.table.replace('\'', "''"),
+            quote_ident(&self.table),
+            condition
+        )
+    }
+
+    fn report_query(&self) -> String {
+        format!(
+            "SELECT line, error, {} FROM {} WHERE error IS NOT NULL ORDER BY line",
+            (0..self.columns.len()).map(|i| self.staging_column(i)).collect::<Vec<_>>().join(", "),
+            STAGING
+        )
+    }
+
+    fn merge(&self) -> String {
+        let columns = (0..self.columns.len()).map(|i| self.staging_column(i)).collect::<Vec<_>>();
+        let mut sql = format!(
+            "INSERT INTO {} ({}) SELECT {} FROM {} WHERE error IS NULL ORDER BY line",
+            quote_ident(&self.table),
+            columns.join(", "),
+            (0..self.columns.len()).map(|i| self.cast(i)).collect::<Vec<_>>().join(", "),
+            STAGING
+        );
+        let key = self.key.iter().map(|key| quote_ident(key)).collect::<Vec<_>>();
+        match self.on_conflict {
+            OnConflict::Reject => {}
+            OnConflict::Skip if key.is_empty() => sql.push_str(" ON CONFLICT DO NOTHING"),
+            OnConflict::Skip => sql.push_str(&format!(" ON CONFLICT ({}) DO NOTHING", key.join(", "))),
+            OnConflict::Update => {
+                let update = columns
+                    .iter()
+                    .filter(|column| !key.contains(column))
+                    .map(|column| format!("{0} = EXCLUDED.{0}", column))
+                    .collect::<Vec<_>>();
+                if key.is_empty() || update.is_empty() {
+                    sql.push_str(" ON CONFLICT DO NOTHING");
+                } else {
+                    sql.push_str(&format!(" ON CONFLICT ({}) DO UPDATE SET {}", key.join(", "), update.join(", ")));
+                }
+            }
+        }
+        sql
+    }
+}
@@ -0,0 +1,150 @@
+// Реестр именованных запросов: SQL и типы параметров объявляются один раз при старте,
+// а prepare на сервере происходит лениво - при первом использовании на каждом соединении.
+//
+//     let statements = Statements::new(Preparation::Persistent)
+//         .declare("insert_todo", "INSERT INTO todo (name, checked) VALUES ($1, $2) RETURNING id", &["VARCHAR", "BOOL"]);
+//     let row = statements.query("insert_todo", &mut conn).await?.bind("name").bind(false).fetch_one(&mut conn).await?;
+//     println!("{:?}", statements.stats());
+//
+// Preparation::Persistent - подготовленный запрос остаётся в кэше соединения (statement_cache_capacity)
+// и переиспользуется: hit - уже был на этом соединении, miss - Parse на сервере, unknown - кэш
+// заполнен и по нему не понять, был ли запрос там (см. prepare).
+// Preparation::Unnamed - запрос не кэшируется и разбирается сервером при каждом выполнении
+// (.persistent(false)), каждое выполнение - miss. Нужен, когда соединение за пулером
+// (pgbouncer pool_mode=transaction) и между запросами может оказаться другим серверным процессом;
+// типы параметров в этом режиме берутся из bind, объявленные не используются.
+
+use crate::error::Error;
+use crate::settings;
+use sqlx::postgres::{PgArguments, PgConnection, PgRow, PgTypeInfo};
+use sqlx::query::{Map, Query};
+use sqlx::{Connection, Executor, FromRow, Postgres};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preparation {
+    Persistent,
+    Unnamed,
+}
+
+impl Preparation {
+    // Unnamed, если в настройках pgbouncer = true
+    pub fn from_settings() -> Result<Self, Error> {
+        Ok(if settings::pgbouncer()? { Preparation::Unnamed } else { Preparation::Persistent })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatementStats {
+    pub name: &'static str,
+    pub hits: u64,
+    pub misses: u64,
+    pub unknown: u64,
+}
+
+struct Declared {
+    sql: String,
+    // имена типов: INT4, VARCHAR, TIMESTAMPTZ, ...
+    parameters: Vec<&'static str>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    unknown: AtomicU64,
+}
+
+// QueryAs в sqlx 0.5 нельзя сделать не persistent, поэтому query_as_with - Query::try_map
+pub type RowTo<T> = fn(PgRow) -> Result<T, sqlx::Error>;
+
+pub struct Statements {
+    preparation: Preparation,
+    declared: BTreeMap<&'static str, Declared>,
+}
+
+impl Statements {
+    pub fn new(preparation: Preparation) -> Self {
+        Statements { preparation, declared: BTreeMap::new() }
+    }
+
+    // повторное объявление имени заменяет запрос
+    pub fn declare(mut self, name: &'static str, sql: &str, parameters: &[&'static str]) -> Self {
+        let declared = Declared {
+            sql: sql.to_string(),
+            parameters: parameters.to_vec(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            unknown: AtomicU64::new(0),
+        };
+        self.declared.insert(name, declared);
+        self
+    }
+
+    pub fn preparation(&self) -> Preparation {
+        self.preparation
+    }
+
+    pub fn sql(&self, name: &str) -> Result<&str, Error> {
+        Ok(&self.get(name)?.sql)
+    }
+
+    // Запрос для bind и выполнения на том же conn
+    pub async fn query(&self, name: &str, conn: &mut PgConnection) -> Result<Query<'_, Postgres, PgArguments>, Error> {
+        let sql = self.prepare(name, conn).await?;
+        Ok(sqlx::query(sql).persistent(self.preparation == Preparation::Persistent))
+    }
+
+    // Строки сразу в T; аргументы заранее, потому что после try_map bind уже нет
+    pub async fn query_as_with<T>(&self, name: &str, conn: &mut PgConnection, arguments: PgArguments) -> Result<Map<'_, Postgres, RowTo<T>, PgArguments>, Error>
+    where
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        let sql = self.prepare(name, conn).await?;
+        let from_row: RowTo<T> = |row| T::from_row(&row);
+        Ok(sqlx::query_with(sql, arguments).persistent(self.preparation == Preparation::Persistent).try_map(from_row))
+    }
+
+    // по имени, для метрик
+    pub fn stats(&self) -> Vec<StatementStats> {
+        self.declared
+            .iter()
+            .map(|(name, declared)| StatementStats {
+                name,
+                hits: declared.hits.load(Ordering::Relaxed),
+                misses: declared.misses.load(Ordering::Relaxed),
+                unknown: declared.unknown.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
+    fn get(&self, name: &str) -> Result<&Declared, Error> {
+        self.declared.get(name).ok_or_else(|| Error::NotFound(format!("statement {}", name)))
+    }
+
+    // Persistent: prepare_with кладёт запрос в кэш соединения (ключ - текст SQL), выполнение
+    // query(sql) на том же соединении берёт его оттуда вместе с объявленными типами параметров.
+    // Что подготовлено на соединении, знает только этот кэш, а снаружи виден лишь его размер:
+    // вырос - miss (Parse), не изменился - hit. В заполненном кэше (settings::STATEMENT_CACHE_CAPACITY,
+    // как в connect_options) Parse вытесняет другой запрос и размер тоже не меняется - такой вызов
+    // считается unknown. Кэш ради счётчиков не трогаем: его очистка - DEALLOCATE и новые Parse
+    // для всех запросов соединения, включая query!/query_as!.
+    async fn prepare(&self, name: &str, conn: &mut PgConnection) -> Result<&str, Error> {
+        let declared = self.get(name)?;
+        if self.preparation == Preparation::Unnamed {
+            declared.misses.fetch_add(1, Ordering::Relaxed);
+            return Ok(&declared.sql);
+        }
+
+        let parameters: Vec<PgTypeInfo> = declared.parameters.iter().map(|name| PgTypeInfo::with_name(name)).collect();
+        let cached = conn.cached_statements_size();
+        conn.prepare_with(&declared.sql, &parameters).await?;
+        // с выключенным кэшем (capacity 0) - каждый раз miss
+        let after = conn.cached_statements_size();
+        if after > cached || after == 0 {
+            declared.misses.fetch_add(1, Ordering::Relaxed);
+        } else if after >= settings::STATEMENT_CACHE_CAPACITY {
+            declared.unknown.fetch_add(1, Ordering::Relaxed);
+        } else {
+            declared.hits.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(&declared.sql)
+    }
+}
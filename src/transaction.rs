@@ -0,0 +1,141 @@
+// Транзакция с уровнем изоляции и повтором при конфликте:
+//
+//     let id = with_transaction(&pool, IsolationLevel::Serializable, |tx| Box::pin(async move {
+//         let id: TodoId = sqlx::query_as("INSERT INTO todo (name) VALUES ($1) RETURNING id").bind("name").fetch_one(&mut *tx).await?;
+//         Ok(id)
+//     })).await?;
+//
+// Ok - COMMIT, Err - ROLLBACK. При SQLSTATE 40001 (serialization_failure) и 40P01 (deadlock_detected),
+// в том числе на COMMIT, транзакция начинается заново и замыкание вызывается ещё раз,
+// поэтому внутри не должно быть побочных эффектов вне базы.
+// Вложенные области внутри транзакции - savepoint().
+
+use crate::error::Error;
+use futures::future::BoxFuture;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsolationLevel {
+    ReadCommitted,
+    RepeatableRead,
+    Serializable,
+}
+
+impl IsolationLevel {
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            IsolationLevel::ReadCommitted => "READ COMMITTED",
+            IsolationLevel::RepeatableRead => "REPEATABLE READ",
+            IsolationLevel::Serializable => "SERIALIZABLE",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Retry {
+    // всего попыток, включая первую
+    pub max_attempts: u32,
+    // задержка перед второй попыткой, дальше удваивается до max_delay
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Retry { max_attempts: 5, base_delay: Duration::from_millis(10), max_delay: Duration::from_secs(1) }
+    }
+}
+
+impl Retry {
+    // экспоненциальная задержка со случайной добавкой до половины, чтобы конфликтующие транзакции разошлись
+    fn delay(&self, attempt: u32) -> Duration {
+        let delay = self.base_delay.saturating_mul(1 << attempt.min(16)).min(self.max_delay);
+        let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().subsec_nanos();
+        delay + delay / 2 * (nanos % 1000) / 1000
+    }
+}
+
+pub async fn with_transaction<T, F>(pool: &PgPool, isolation: IsolationLevel, f: F) -> Result<T, Error>
+where
+    F: for<'c> FnMut(&'c mut Transaction<'static, Postgres>) -> BoxFuture<'c, Result<T, Error>>,
+{
+    with_transaction_retry(pool, isolation, Retry::default(), f).await
+}
+
+pub async fn with_transaction_retry<T, F>(pool: &PgPool, isolation: IsolationLevel, retry: Retry, mut f: F) -> Result<T, Error>
+where
+    F: for<'c> FnMut(&'c mut Transaction<'static, Postgres>) -> BoxFuture<'c, Result<T, Error>>,
+{
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        match run_once(pool, isolation, &mut f).await {
+            Err(err) if is_conflict(&err) && attempt < retry.max_attempts => {
+                async_std::task::sleep(retry.delay(attempt - 1)).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+async fn run_once<T, F>(pool: &PgPool, isolation: IsolationLevel, f: &mut F) -> Result<T, Error>
+where
+    F: for<'c> FnMut(&'c mut Transaction<'static, Postgres>) -> BoxFuture<'c, Result<T, Error>>,
+{
+    let mut tx = pool.begin().await?;
+    // должна быть первой командой транзакции
+    tx.execute(format!("SET TRANSACTION ISOLATION LEVEL {}", isolation.as_sql()).as_str()).await?;
+    match f(&mut tx).await {
+        Ok(value) => {
+            tx.commit().await?;
+            Ok(value)
+        }
+        Err(err) => {
+            // ошибка замыкания важнее ошибки ROLLBACK (при обрыве соединения откатит сервер)
+            let _ = tx.rollback().await;
+            Err(err)
+        }
+    }
+}
+
+fn is_conflict(err: &Error) -> bool {
+    matches!(err, Error::SerializationFailure(_) | Error::Deadlock(_))
+}
+
+// Вложенная атомарная область внутри транзакции:
+//
+//     savepoint(&mut tx, |tx| Box::pin(async move {
+//         sqlx::query("INSERT ...").execute(&mut *tx).await?;
+//         Ok(())
+//     })).await?;
+//
+// SAVEPOINT с уникальным именем, Ok - RELEASE SAVEPOINT (изменения остаются в транзакции и
+// откатятся вместе с ней), Err - ROLLBACK TO SAVEPOINT и RELEASE: изменения области отменены,
+// транзакция снова рабочая (даже после ошибки SQL) и вызывающий код может продолжать.
+// Если не удался сам ROLLBACK TO, возвращается его ошибка - транзакцию продолжать нельзя.
+// Области вкладываются: savepoint внутри замыкания.
+pub async fn savepoint<'t, T, F>(tx: &mut Transaction<'t, Postgres>, f: F) -> Result<T, Error>
+where
+    F: for<'c> FnOnce(&'c mut Transaction<'t, Postgres>) -> BoxFuture<'c, Result<T, Error>>,
+{
+    let name = savepoint_name();
+    tx.execute(format!("SAVEPOINT {}", name).as_str()).await?;
+    match f(tx).await {
+        Ok(value) => {
+            tx.execute(format!("RELEASE SAVEPOINT {}", name).as_str()).await?;
+            Ok(value)
+        }
+        Err(err) => {
+            tx.execute(format!("ROLLBACK TO SAVEPOINT {0}; RELEASE SAVEPOINT {0}", name).as_str()).await?;
+            Err(err)
+        }
+    }
+}
+
+// sp_1, sp_2, ... - уникально в процессе, поэтому вложенные области не перекрывают друг друга
+fn savepoint_name() -> String {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    format!("sp_{}", NEXT.fetch_add(1, Ordering::Relaxed))
+}
@@ -0,0 +1,129 @@
+// Экранирование CSV и имён - без базы; COPY против базы: cargo test --test bulk -- --ignored
+
+use sqlx::{Connection, PgConnection};
+use sqlx_example::bulk::{bulk_upsert, quote_ident, BulkLoader, BulkUpsert, CopyRow, CopyValue, Format, RowWriter};
+use sqlx_example::{settings, Error};
+
+struct Text(Option<&'static str>);
+
+impl CopyRow for Text {
+    const COLUMNS: &'static [&'static str] = &["name"];
+    fn write(&self, row: &mut RowWriter<'_>) {
+        row.field(&self.0);
+    }
+}
+
+fn csv(value: Option<&'static str>) -> String {
+    let mut buf = Default::default();
+    BulkLoader::<Text>::new("todo").format(Format::Csv).encode(&Text(value), &mut buf).unwrap();
+    String::from_utf8(buf.to_vec()).unwrap()
+}
+
+#[test]
+fn csv_empty_string_is_not_null() {
+    assert_eq!(csv(None), "\n");
+    assert_eq!(csv(Some("")), "\"\"\n");
+    assert_eq!(csv(Some("plain")), "plain\n");
+}
+
+#[test]
+fn csv_special_characters_are_quoted() {
+    assert_eq!(csv(Some("a,b")), "\"a,b\"\n");
+    assert_eq!(csv(Some("say \"hi\"")), "\"say \"\"hi\"\"\"\n");
+    assert_eq!(csv(Some("two\nlines")), "\"two\nlines\"\n");
+    assert_eq!(csv(Some("cr\rlf")), "\"cr\rlf\"\n");
+    // пробелы по краям в кавычках: их теряют другие читатели CSV
+    assert_eq!(csv(Some(" padded ")), "\" padded \"\n");
+    // \. в начале строки - конец данных COPY
+    assert_eq!(csv(Some("\\.")), "\"\\.\"\n");
+}
+
+#[test]
+fn float_text_uses_postgres_spelling() {
+    assert_eq!(f64::NAN.to_text().as_deref(), Some("NaN"));
+    assert_eq!(f64::INFINITY.to_text().as_deref(), Some("Infinity"));
+    assert_eq!(f64::NEG_INFINITY.to_text().as_deref(), Some("-Infinity"));
+    assert_eq!(f32::INFINITY.to_text().as_deref(), Some("Infinity"));
+    assert_eq!(1.5_f64.to_text().as_deref(), Some("1.5"));
+}
+
+#[test]
+fn identifiers_are_quoted() {
+    assert_eq!(quote_ident("todo"), "\"todo\"");
+    assert_eq!(quote_ident("public.todo"), "\"public\".\"todo\"");
+    assert_eq!(quote_ident("we\"ird"), "\"we\"\"ird\"");
+}
+
+// write пишет не все колонки
+struct Short;
+
+impl CopyRow for Short {
+    const COLUMNS: &'static [&'static str] = &["name", "checked"];
+    fn write(&self, row: &mut RowWriter<'_>) {
+        row.field(&"only name");
+    }
+}
+
+#[test]
+fn wrong_field_count_is_an_error() {
+    let mut buf = Default::default();
+    let err = BulkLoader::<Short>::new("todo").encode(&Short, &mut buf).unwrap_err();
+    assert!(matches!(err, Error::Validation { field: "columns", .. }), "{:?}", err);
+    assert!(buf.is_empty());
+}
+
+#[tokio::test]
+#[ignore]
+async fn wrong_field_count_aborts_copy() {
+    let mut conn = PgConnection::connect(&settings::config().unwrap()).await.unwrap();
+    for format in [Format::Binary, Format::Csv] {
+        let err = BulkLoader::<Short>::new("todo").format(format).load(&mut conn, [Short]).await.unwrap_err();
+        assert!(matches!(err, Error::Validation { field: "columns", .. }), "{:?}", err);
+        // COPY прерван, соединение снова принимает запросы
+        let one: i32 = sqlx::query_scalar("SELECT 1").fetch_one(&mut conn).await.unwrap();
+        assert_eq!(one, 1);
+    }
+}
+
+#[tokio::test]
+#[ignore]
+async fn csv_values_round_trip() {
+    let mut conn = PgConnection::connect(&settings::config().unwrap()).await.unwrap();
+    let mut tx = conn.begin().await.unwrap();
+    sqlx::query("CREATE TEMPORARY TABLE bulk_text (name TEXT) ON COMMIT DROP").execute(&mut tx).await.unwrap();
+    let values = [None, Some(""), Some("a,b"), Some("say \"hi\""), Some("two\nlines"), Some(" padded "), Some("\\."), Some("\\N")];
+    let rows: Vec<Text> = values.iter().map(|value| Text(*value)).collect();
+    let loaded = BulkLoader::<Text>::new("bulk_text").format(Format::Csv).load(&mut tx, &rows).await.unwrap();
+    assert_eq!(loaded, values.len() as u64);
+
+    let names: Vec<Option<String>> = sqlx::query_scalar("SELECT name FROM bulk_text").fetch_all(&mut tx).await.unwrap();
+    assert_eq!(names, values.iter().map(|value| value.map(String::from)).collect::<Vec<_>>());
+}
+
+async fn upsert_table(conn: &mut PgConnection) -> sqlx::Transaction<'_, sqlx::Postgres> {
+    let mut tx = conn.begin().await.unwrap();
+    sqlx::query("CREATE TEMPORARY TABLE bulk_upsert_test (id INT4 PRIMARY KEY, name TEXT) ON COMMIT DROP").execute(&mut tx).await.unwrap();
+    tx
+}
+
+fn duplicate_ids() -> BulkUpsert {
+    bulk_upsert("bulk_upsert_test").column("id", vec![1, 1]).column("name", vec!["first".to_string(), "second".to_string()])
+}
+
+// Один ключ дважды в пачке: DO UPDATE падает целиком, DO NOTHING вставляет первую строку
+#[tokio::test]
+#[ignore]
+async fn duplicate_key_in_one_upsert_batch() {
+    let mut conn = PgConnection::connect(&settings::config().unwrap()).await.unwrap();
+
+    let mut tx = upsert_table(&mut conn).await;
+    let err = duplicate_ids().on_conflict_update(&["id"], &["name"]).fetch_ids::<i32, _>(&mut tx).await.unwrap_err();
+    assert_eq!(err.database_error().map(|err| err.code.as_str()), Some("21000"), "{:?}", err);
+    tx.rollback().await.unwrap();
+
+    let mut tx = upsert_table(&mut conn).await;
+    let ids: Vec<i32> = duplicate_ids().on_conflict("(id) DO NOTHING").fetch_ids(&mut tx).await.unwrap();
+    assert_eq!(ids, vec![1]);
+    let name: String = sqlx::query_scalar("SELECT name FROM bulk_upsert_test").fetch_one(&mut tx).await.unwrap();
+    assert_eq!(name, "first");
+}
@@ -0,0 +1,152 @@
+// Маршрутизация проверяется на ленивых пулах без соединения;
+// check_health и повтор чтения против базы: cargo test --test db -- --ignored
+
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::PgPool;
+use sqlx_example::db::{Db, Session};
+use sqlx_example::ids::TodoId;
+use sqlx_example::repo::{self, TodoFilter, Todos};
+use sqlx_example::{settings, Error};
+use std::time::Duration;
+
+// порт 1 - соединиться нельзя
+fn unreachable() -> PgPool {
+    PgPoolOptions::new()
+        .connect_timeout(Duration::from_millis(500))
+        .connect_lazy_with(PgConnectOptions::new().host("localhost").port(1))
+}
+
+fn db(replicas: usize) -> Db {
+    Db::new(unreachable(), (0..replicas).map(|_| unreachable()).collect())
+}
+
+fn db_with_ejected(replicas: usize) -> Db {
+    let db = db(replicas);
+    for index in 0..replicas {
+        db.eject(db.replica(index).unwrap());
+    }
+    db
+}
+
+// индекс реплики, None - primary
+fn route(db: &Db, pool: &PgPool) -> Option<usize> {
+    (0..).map_while(|index| db.replica(index)).position(|replica| std::ptr::eq(replica, pool))
+}
+
+#[tokio::test]
+async fn readers_round_robin_over_healthy_replicas() {
+    let db = db(3);
+    let routes: Vec<_> = (0..6).map(|_| route(&db, db.reader())).collect();
+    assert_eq!(routes, vec![Some(0), Some(1), Some(2), Some(0), Some(1), Some(2)]);
+
+    db.eject(db.replica(1).unwrap());
+    assert_eq!(db.healthy_replicas(), 2);
+    let routes: Vec<_> = (0..4).map(|_| route(&db, db.reader())).collect();
+    assert!(routes.iter().all(|route| *route == Some(0) || *route == Some(2)), "{:?}", routes);
+
+    // primary не исключается
+    db.eject(db.writer());
+    assert_eq!(route(&db, db.writer()), None);
+}
+
+#[tokio::test]
+async fn reads_go_to_primary_without_healthy_replicas() {
+    let db = db(0);
+    assert!(std::ptr::eq(db.reader(), db.writer()));
+
+    let db = db_with_ejected(2);
+    assert_eq!(db.healthy_replicas(), 0);
+    assert!(std::ptr::eq(db.reader(), db.writer()));
+}
+
+#[tokio::test]
+async fn ejected_replica_comes_back() {
+    let db = db(1).eject_for(Duration::from_millis(50));
+    db.eject(db.replica(0).unwrap());
+    assert_eq!(route(&db, db.reader()), None);
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert_eq!(route(&db, db.reader()), Some(0));
+}
+
+// запись без обращения к базе
+async fn write_ok(session: &Session<'_>) {
+    session.write(|_| Box::pin(async { Ok(()) })).await.unwrap();
+}
+
+#[tokio::test]
+async fn session_reads_from_primary_after_write() {
+    let db = db(2);
+    let session = db.session();
+    assert_eq!(route(&db, session.reader()), Some(0));
+    assert!(!session.is_pinned());
+
+    // неудачная запись не закрепляет чтения
+    let err = session.write(|pool| Box::pin(repo::delete(pool, TodoId(1)))).await.unwrap_err();
+    assert!(matches!(err, Error::ConnectionLost(_) | Error::ConnectingToDatabase(_)), "{:?}", err);
+    assert!(!session.is_pinned());
+
+    write_ok(&session).await;
+    assert!(session.is_pinned());
+    assert_eq!(route(&db, session.reader()), None);
+    assert_eq!(route(&db, session.reader()), None);
+
+    // у другой сессии своя история записей
+    assert!(!db.session().is_pinned());
+
+    let db = db.pin_reads_after_write(false);
+    let session = db.session();
+    write_ok(&session).await;
+    assert!(!session.is_pinned());
+    assert!(route(&db, session.reader()).is_some());
+}
+
+// повтор тоже упал - исключены обе реплики
+#[tokio::test]
+async fn failed_retry_ejects_second_replica() {
+    let db = db(2);
+    let err = db.read(|pool| Box::pin(repo::find(pool, TodoId(1)))).await.unwrap_err();
+    assert!(matches!(err, Error::ConnectionLost(_) | Error::ConnectingToDatabase(_)), "{:?}", err);
+    assert_eq!(db.healthy_replicas(), 0);
+    assert_eq!(route(&db, db.reader()), None);
+}
+
+async fn live() -> PgPool {
+    PgPoolOptions::new().max_connections(2).connect_with(settings::connect_options().unwrap()).await.unwrap()
+}
+
+#[tokio::test]
+#[ignore]
+async fn failed_replica_is_ejected_and_read_retried() {
+    let db = Db::new(live().await, vec![unreachable(), live().await]);
+    let id = repo::insert(db.writer(), "db read retry", false).await.unwrap();
+
+    // первое чтение попадает на недоступную реплику и повторяется на живой
+    let todo = db.read(|pool| Box::pin(repo::find(pool, id))).await.unwrap();
+    assert_eq!(todo.and_then(|todo| todo.name).as_deref(), Some("db read retry"));
+    assert_eq!(db.healthy_replicas(), 1);
+    for _ in 0..3 {
+        assert_eq!(route(&db, db.reader()), Some(1));
+    }
+
+    assert!(repo::delete(db.writer(), id).await.unwrap());
+}
+
+#[tokio::test]
+#[ignore]
+async fn check_health_ejects_unreachable_replicas() {
+    let db = Db::new(live().await, vec![unreachable(), live().await]).health_timeout(Duration::from_secs(2));
+    db.check_health().await;
+    assert_eq!(db.healthy_replicas(), 1);
+    assert_eq!(route(&db, db.reader()), Some(1));
+
+    // запись в сессии, чтение своей строки с primary
+    let session = db.session();
+    let todos = Todos(&session);
+    let id = todos.insert("db test", false).await.unwrap();
+    assert!(session.is_pinned());
+    assert_eq!(todos.find(id).await.unwrap().and_then(|todo| todo.name).as_deref(), Some("db test"));
+    // фильтр живёт меньше сессии
+    let found = todos.search(&TodoFilter { ids: Some(vec![id.0]), ..TodoFilter::default() }).await.unwrap();
+    assert_eq!(found.len(), 1);
+    assert!(todos.delete(id).await.unwrap());
+}
@@ -0,0 +1,19 @@
+// Классификация ошибок для повторов - без базы
+
+use sqlx_example::Error;
+use std::io;
+
+#[test]
+fn connection_lost_is_not_retryable() {
+    let err = Error::from(sqlx::Error::Io(io::Error::from(io::ErrorKind::ConnectionReset)));
+    assert!(err.is_connection_lost());
+    // COMMIT мог примениться - повтор транзакции не безопасен
+    assert!(!err.is_retryable());
+}
+
+#[test]
+fn pool_timeout_is_retryable() {
+    let err = Error::from(sqlx::Error::PoolTimedOut);
+    assert!(err.is_retryable());
+    assert!(!err.is_connection_lost());
+}
@@ -0,0 +1,26 @@
+// Выгрузка против базы: cargo test --test export -- --ignored
+
+use flate2::read::GzDecoder;
+use sqlx::{Connection, PgConnection};
+use sqlx_example::copy_out::{self, Format, Source};
+use sqlx_example::settings;
+use std::io::Read;
+
+#[tokio::test]
+#[ignore]
+async fn gzip_output_round_trips() {
+    let mut conn = PgConnection::connect(&settings::config().unwrap()).await.unwrap();
+    let source = Source::Query("SELECT n, 'row ' || n AS name FROM generate_series(1, 10000) n".into());
+
+    let mut plain = Vec::new();
+    let written = copy_out::export(&mut conn, &source, Format::Csv, &mut plain).await.unwrap();
+    assert_eq!(written, plain.len() as u64);
+
+    let (gzip_written, compressed) = copy_out::export_gzip(&mut conn, &source, Format::Csv, Vec::new()).await.unwrap();
+    assert_eq!(gzip_written, written);
+    let mut unpacked = Vec::new();
+    // без хвоста gzip read_to_end вернёт ошибку
+    GzDecoder::new(compressed.as_slice()).read_to_end(&mut unpacked).unwrap();
+    assert_eq!(unpacked, plain);
+    assert!(plain.starts_with(b"n,name\n1,row 1\n"));
+}
@@ -0,0 +1,131 @@
+// Старые сообщения из queue должны декодироваться текущим кодом.
+// Фикстуры в tests/fixtures/messages - реальные формы payload прошлых версий, не менять.
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx_example::message::{Envelope, Error, Payload, Registry};
+
+// v1: {"to": "a@b", "subject"}
+// v2: to стал списком
+// v3: добавлены cc и body
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct SendEmail {
+    to: Vec<String>,
+    cc: Vec<String>,
+    subject: String,
+    body: Option<String>,
+}
+
+impl Payload for SendEmail {
+    const TYPE: &'static str = "send_email";
+    const VERSION: u32 = 3;
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Cleanup {
+    older_than_days: u32,
+}
+
+impl Payload for Cleanup {
+    const TYPE: &'static str = "cleanup";
+    const VERSION: u32 = 1;
+}
+
+#[derive(Debug, PartialEq)]
+enum Job {
+    SendEmail(SendEmail),
+    Cleanup(Cleanup),
+}
+
+fn registry() -> Registry<Job> {
+    Registry::new()
+        .upcast("send_email", 1, |mut payload| {
+            let to = payload["to"].take();
+            if !to.is_string() {
+                return Err(format!("`to` must be a string, got {}", to));
+            }
+            payload["to"] = json!([to]);
+            Ok(payload)
+        })
+        .upcast("send_email", 2, |mut payload| {
+            payload["cc"] = json!([]);
+            payload["body"] = json!(null);
+            Ok(payload)
+        })
+        .register(Job::SendEmail)
+        .register(Job::Cleanup)
+}
+
+fn fixture(name: &str) -> Envelope {
+    let path = format!("{}/tests/fixtures/messages/{}", env!("CARGO_MANIFEST_DIR"), name);
+    serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap()
+}
+
+fn expected_email() -> Job {
+    Job::SendEmail(SendEmail {
+        to: vec!["cat@example.com".into()],
+        cc: vec![],
+        subject: "pet me".into(),
+        body: None,
+    })
+}
+
+#[test]
+fn every_send_email_fixture_decodes_to_current_version() {
+    let registry = registry();
+    for name in &["send_email_v1.json", "send_email_v2.json", "send_email_v3.json"] {
+        assert_eq!(registry.decode(&fixture(name)).unwrap(), expected_email(), "{}", name);
+    }
+}
+
+#[test]
+fn upcasters_run_in_order() {
+    let upcasted = registry().upcast_envelope(&fixture("send_email_v1.json")).unwrap();
+    assert_eq!(upcasted, fixture("send_email_v3.json"));
+}
+
+#[test]
+fn current_version_needs_no_upcaster() {
+    assert_eq!(
+        registry().decode(&fixture("cleanup_v1.json")).unwrap(),
+        Job::Cleanup(Cleanup { older_than_days: 30 })
+    );
+}
+
+#[test]
+fn encoded_envelope_round_trips() {
+    let envelope = Envelope::new(&Cleanup { older_than_days: 7 }).unwrap();
+    assert_eq!(serde_json::to_value(&envelope).unwrap(), json!({"type": "cleanup", "version": 1, "payload": {"older_than_days": 7}}));
+    assert_eq!(registry().decode(&envelope).unwrap(), Job::Cleanup(Cleanup { older_than_days: 7 }));
+}
+
+#[test]
+fn unknown_type_is_an_error() {
+    let envelope = Envelope { kind: "resize_image".into(), version: 1, payload: json!({}) };
+    assert!(matches!(registry().decode(&envelope), Err(Error::UnknownType { kind }) if kind == "resize_image"));
+}
+
+#[test]
+fn version_from_the_future_is_an_error() {
+    let mut envelope = fixture("send_email_v3.json");
+    envelope.version = 4;
+    match registry().decode(&envelope) {
+        Err(Error::UnsupportedVersion { version, supported, .. }) => {
+            assert_eq!(version, 4);
+            assert_eq!(supported, vec![1, 2, 3]);
+        }
+        other => panic!("expected UnsupportedVersion, got {:?}", other),
+    }
+}
+
+#[test]
+fn failing_upcaster_is_an_error() {
+    let envelope = Envelope { kind: "send_email".into(), version: 1, payload: json!({"to": 42, "subject": "x"}) };
+    assert!(matches!(registry().decode(&envelope), Err(Error::Upcast { from_version: 1, .. })));
+}
+
+#[test]
+fn payload_of_wrong_shape_is_an_error() {
+    let envelope = Envelope { kind: "cleanup".into(), version: 1, payload: json!({"older_than_days": "soon"}) };
+    assert!(matches!(registry().decode(&envelope), Err(Error::InvalidPayload { version: 1, .. })));
+}
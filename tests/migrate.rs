@@ -0,0 +1,50 @@
+// migrate::run с `-- no-transaction` во временной базе на сервере из settings:
+// cargo test --test migrate -- --ignored
+
+use sqlx::migrate::{MigrateDatabase, Migrator};
+use sqlx::postgres::PgPoolOptions;
+use sqlx::Executor;
+use sqlx_example::{migrate, settings};
+use std::path::PathBuf;
+
+fn migrations(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("migrate_{}_{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("0001_values.sql"), "CREATE TABLE migrate_test (value INT4); INSERT INTO migrate_test VALUES (1), (1);").unwrap();
+    // уникальный индекс по повторяющимся значениям не строится и остаётся INVALID
+    std::fs::write(
+        dir.join("0002_unique_index.sql"),
+        "-- no-transaction\nCREATE UNIQUE INDEX CONCURRENTLY IF NOT EXISTS migrate_test_value ON migrate_test (value);",
+    )
+    .unwrap();
+    dir
+}
+
+#[tokio::test]
+#[ignore]
+async fn invalid_concurrent_index_fails_again_on_rerun() {
+    let dir = migrations("invalid_index");
+    let database = format!("{}_migrate_test_{}", settings::dbname().unwrap(), std::process::id());
+    let config = settings::direct_config_for_dbname(&database).unwrap();
+    sqlx::Postgres::create_database(&config).await.unwrap();
+    let pool = PgPoolOptions::new().max_connections(1).connect(&config).await.unwrap();
+    let migrator = Migrator::new(dir.as_path()).await.unwrap();
+
+    let err = migrate::run(&migrator, &pool).await.unwrap_err().to_string();
+    assert!(err.contains("invalid index(es) public.migrate_test_value"), "{}", err);
+    assert!(err.contains("could not create unique index"), "{}", err);
+
+    // ручной повтор без DROP INDEX: IF NOT EXISTS пропускает индекс, но миграция не проходит
+    sqlx::query("DELETE FROM _sqlx_migrations WHERE version = 2").execute(&pool).await.unwrap();
+    let err = migrate::run(&migrator, &pool).await.unwrap_err().to_string();
+    assert!(err.contains("invalid index(es) public.migrate_test_value"), "{}", err);
+
+    // после DROP INDEX и исправления данных проходит
+    sqlx::query("DELETE FROM _sqlx_migrations WHERE version = 2").execute(&pool).await.unwrap();
+    pool.execute("DROP INDEX migrate_test_value; DELETE FROM migrate_test").await.unwrap();
+    migrate::run(&migrator, &pool).await.unwrap();
+
+    pool.close().await;
+    sqlx::Postgres::drop_database(&config).await.unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+}
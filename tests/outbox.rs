@@ -0,0 +1,48 @@
+// Relay против базы с миграциями: cargo test --test outbox -- --ignored
+
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPoolOptions;
+use sqlx::PgPool;
+use sqlx_example::outbox::{self, Destination, Notification};
+use sqlx_example::{listener, settings};
+use std::time::Duration;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Note {
+    text: String,
+}
+
+impl sqlx_example::message::Payload for Note {
+    const TYPE: &'static str = "outbox_test_note";
+    const VERSION: u32 = 1;
+}
+
+async fn pool() -> PgPool {
+    PgPoolOptions::new().max_connections(2).connect_with(settings::connect_options().unwrap()).await.unwrap()
+}
+
+// Сообщение больше лимита NOTIFY (8000 байт) не блокирует следующие
+#[tokio::test]
+#[ignore]
+async fn oversized_message_does_not_block_relay() {
+    let pool = pool().await;
+    let mut listener = listener::connect().await.unwrap();
+    listener.listen("outbox_test").await.unwrap();
+
+    let large = outbox::add(&pool, "outbox_test", &Note { text: "x".repeat(10_000) }).await.unwrap();
+    let small = outbox::add(&pool, "outbox_test", &Note { text: "after".into() }).await.unwrap();
+    while outbox::relay_once(&pool, Destination::Notify, 100).await.unwrap() > 0 {}
+
+    let mut received = Vec::new();
+    while received.len() < 2 {
+        let notification = tokio::time::timeout(Duration::from_secs(5), listener.recv()).await.unwrap().unwrap();
+        let notification: Notification = serde_json::from_str(notification.payload()).unwrap();
+        received.push(notification.id);
+    }
+    assert_eq!(received, vec![large, small]);
+
+    let row = outbox::fetch(&pool, large).await.unwrap().unwrap();
+    assert!(row.sent_at.is_some());
+    assert_eq!(row.message.0.decode::<Note>().unwrap().text.len(), 10_000);
+    assert!(outbox::fetch(&pool, small).await.unwrap().unwrap().sent_at.is_some());
+}
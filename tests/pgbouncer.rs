@@ -0,0 +1,115 @@
+// Режим pgbouncer = true против локального pgbouncer в pool_mode=transaction:
+//
+//     cargo test --test pgbouncer -- --ignored
+//
+// pgbouncer.ini (порт 6432, Postgres из settings/settings.toml на 5432):
+//
+//     [databases]
+//     rust = host=localhost port=5432 dbname=rust
+//     [pgbouncer]
+//     listen_port = 6432
+//     auth_type = plain
+//     auth_file = userlist.txt          ; "rust" "job_queue"
+//     pool_mode = transaction
+//     default_pool_size = 2
+//     max_prepared_statements = 100
+//
+// Нужен pgbouncer 1.21+ с max_prepared_statements > 0 (см. README): sqlx 0.5 и без кэша отправляет Parse
+// именованным запросом (sqlx_s_N) отдельно от Bind, и только pgbouncer с поддержкой
+// prepared statements сопоставляет их на разных серверных соединениях.
+// Порты: PGBOUNCER_PORT (6432) и PGBOUNCER_DIRECT_PORT (5432).
+
+use futures::future::try_join_all;
+use sqlx::postgres::PgPoolOptions;
+use sqlx::{PgPool, Row};
+use sqlx_example::ids::TodoId;
+use sqlx_example::statements::{Preparation, Statements};
+use sqlx_example::transaction::{with_transaction, IsolationLevel};
+use sqlx_example::{listener, repo, settings};
+use std::sync::Once;
+use std::time::Duration;
+
+static SETUP: Once = Once::new();
+
+// до первого чтения настроек: те же ключи, что задал бы пользователь
+fn setup() {
+    SETUP.call_once(|| {
+        std::env::set_var("APP_PGBOUNCER", "true");
+        std::env::set_var("APP_PORT", std::env::var("PGBOUNCER_PORT").unwrap_or_else(|_| "6432".into()));
+        std::env::set_var("APP_DIRECT_PORT", std::env::var("PGBOUNCER_DIRECT_PORT").unwrap_or_else(|_| "5432".into()));
+    });
+}
+
+// клиентских соединений больше, чем серверных в pgbouncer (default_pool_size = 2)
+async fn pool() -> PgPool {
+    setup();
+    assert!(settings::pgbouncer().unwrap());
+    PgPoolOptions::new().max_connections(8).connect_with(settings::connect_options().unwrap()).await.unwrap()
+}
+
+// бинарники, которые подключаются по URL (import, export, codegen, ...), тоже без кэша
+#[test]
+fn connection_url_disables_statement_cache() {
+    setup();
+    let config = settings::config().unwrap();
+    assert!(config.ends_with("?statement-cache-capacity=0"), "{}", config);
+    config.parse::<sqlx::postgres::PgConnectOptions>().unwrap();
+}
+
+#[tokio::test]
+#[ignore]
+async fn parameterized_queries_share_server_connections() {
+    let pool = pool().await;
+    let tasks = (0..16).map(|task| {
+        let pool = pool.clone();
+        async move {
+            for i in 0..20 {
+                let sum: i32 = sqlx::query_scalar("SELECT $1::INT4 + $2::INT4").bind(task).bind(i).fetch_one(&pool).await?;
+                assert_eq!(sum, task + i);
+                repo::find(&pool, TodoId(1)).await?;
+            }
+            Ok::<_, sqlx_example::Error>(())
+        }
+    });
+    try_join_all(tasks).await.unwrap();
+}
+
+#[tokio::test]
+#[ignore]
+async fn statements_and_transactions() {
+    let pool = pool().await;
+    let statements = Statements::new(Preparation::from_settings().unwrap()).declare("double", "SELECT $1 * 2", &["INT4"]);
+    assert_eq!(statements.preparation(), Preparation::Unnamed);
+
+    for i in 0..10 {
+        let mut conn = pool.acquire().await.unwrap();
+        let query = statements.query("double", &mut conn).await.unwrap();
+        let row = query.bind(i).fetch_one(&mut conn).await.unwrap();
+        assert_eq!(row.get::<i32, _>(0), i * 2);
+    }
+
+    let name = with_transaction(&pool, IsolationLevel::RepeatableRead, |tx| {
+        Box::pin(async move {
+            let id = repo::insert(&mut *tx, "pgbouncer", false).await?;
+            let todo = repo::find(&mut *tx, id).await?;
+            repo::delete(&mut *tx, id).await?;
+            Ok(todo.and_then(|todo| todo.name))
+        })
+    })
+    .await
+    .unwrap();
+    assert_eq!(name.as_deref(), Some("pgbouncer"));
+}
+
+#[tokio::test]
+#[ignore]
+async fn listener_uses_direct_connection() {
+    let pool = pool().await;
+    let mut listener = listener::connect().await.unwrap();
+    listener.listen("pgbouncer_test").await.unwrap();
+
+    // NOTIFY можно отправить через pgbouncer
+    sqlx::query("SELECT pg_notify('pgbouncer_test', $1)").bind("hello").execute(&pool).await.unwrap();
+    let notification = tokio::time::timeout(Duration::from_secs(5), listener.recv()).await.unwrap().unwrap();
+    assert_eq!(notification.payload(), "hello");
+}
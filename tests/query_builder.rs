@@ -0,0 +1,78 @@
+// SQL собирается без базы; search против базы: cargo test --test query_builder -- --ignored
+
+use sqlx::{Connection, PgConnection};
+use sqlx_example::query_builder::{escape_like, select, Op, Order, Table};
+use sqlx_example::repo::{self, TodoFilter, TODO};
+use sqlx_example::{settings, Error};
+
+const POSTS: Table = Table { name: "posts", columns: &["id", "title"] };
+
+#[test]
+fn placeholders_are_numbered_in_call_order() {
+    let query = select(&TODO)
+        .columns(&["id", "name"])
+        .filter("checked", Op::Eq, true)
+        .filter_opt("name", Op::ILike, None::<String>)
+        .filter_opt("name", Op::ILike, Some("%cat%".to_string()))
+        .any("id", vec![1, 2, 3])
+        .is_not_null("created_at")
+        .order_by("created_at", Order::Desc)
+        .limit(10)
+        .offset(20);
+    assert_eq!(
+        query.sql(),
+        r#"SELECT "id", "name" FROM "todo" WHERE "checked" = $1 AND "name" ILIKE $2 AND "id" = ANY($3) AND "created_at" IS NOT NULL ORDER BY "created_at" DESC LIMIT $4 OFFSET $5"#
+    );
+}
+
+#[test]
+fn no_filters() {
+    assert_eq!(select(&POSTS).sql(), r#"SELECT "id", "title" FROM "posts""#);
+}
+
+#[test]
+fn unknown_column_is_rejected() {
+    let result = select(&TODO).filter("id", Op::Eq, 1).order_by("name; DROP TABLE todo", Order::Asc).build();
+    match result {
+        Err(Error::Validation { field: "column", message }) => assert_eq!(message, "name; DROP TABLE todo is not a column of todo"),
+        Err(err) => panic!("unexpected error {:?}", err),
+        Ok((sql, _)) => panic!("built {}", sql),
+    }
+}
+
+#[test]
+fn like_wildcards_are_escaped() {
+    assert_eq!(escape_like(r"50%_off\"), r"50\%\_off\\");
+}
+
+#[tokio::test]
+#[ignore]
+async fn search_todo() {
+    let mut conn = PgConnection::connect(&settings::config().unwrap()).await.unwrap();
+    let mut tx = conn.begin().await.unwrap();
+    let marker = format!("query_builder_{}%", std::process::id());
+    let first = repo::insert(&mut tx, &format!("{} first", marker), true).await.unwrap();
+    let second = repo::insert(&mut tx, &format!("{} second", marker), false).await.unwrap();
+    // % в маркере - обычный символ, а не шаблон
+    repo::insert(&mut tx, &format!("{} other", marker.replace('%', "x")), true).await.unwrap();
+
+    let filter = TodoFilter { name_contains: Some(marker.to_uppercase()), ..Default::default() };
+    let found: Vec<_> = repo::search(&mut tx, &filter).await.unwrap().into_iter().map(|todo| todo.id).collect();
+    assert_eq!(found, vec![first, second]);
+
+    let filter = TodoFilter {
+        name_contains: Some(marker.clone()),
+        sort: Some(("name".to_string(), Order::Desc)),
+        limit: Some(1),
+        ..Default::default()
+    };
+    let found: Vec<_> = repo::search(&mut tx, &filter).await.unwrap().into_iter().map(|todo| todo.id).collect();
+    assert_eq!(found, vec![second]);
+
+    let filter = TodoFilter { name_contains: Some(marker.clone()), checked: Some(true), ids: Some(vec![first.0, second.0]), ..Default::default() };
+    let found: Vec<_> = repo::search(&mut tx, &filter).await.unwrap().into_iter().map(|todo| todo.id).collect();
+    assert_eq!(found, vec![first]);
+
+    let filter = TodoFilter { sort: Some(("password".to_string(), Order::Asc)), ..Default::default() };
+    assert!(matches!(repo::search(&mut tx, &filter).await, Err(Error::Validation { .. })));
+}
@@ -0,0 +1,144 @@
+// Нужна база из settings/settings.toml: cargo test --test savepoint -- --ignored
+// Каждый тест работает во временной таблице внутри своей транзакции и ничего не коммитит.
+
+use sqlx::{Connection, PgConnection, Postgres, Transaction};
+use sqlx_example::settings;
+use sqlx_example::transaction::savepoint;
+use sqlx_example::Error;
+
+async fn connect() -> PgConnection {
+    PgConnection::connect(&settings::config().unwrap()).await.unwrap()
+}
+
+async fn begin(conn: &mut PgConnection) -> Transaction<'_, Postgres> {
+    let mut tx = conn.begin().await.unwrap();
+    sqlx::query("CREATE TEMP TABLE savepoint_test (name TEXT PRIMARY KEY)").execute(&mut tx).await.unwrap();
+    tx
+}
+
+async fn names(tx: &mut Transaction<'_, Postgres>) -> Vec<String> {
+    sqlx::query_scalar("SELECT name FROM savepoint_test ORDER BY name").fetch_all(tx).await.unwrap()
+}
+
+async fn insert(tx: &mut Transaction<'_, Postgres>, name: &str) -> Result<(), Error> {
+    sqlx::query("INSERT INTO savepoint_test (name) VALUES ($1)").bind(name).execute(tx).await?;
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore]
+async fn released_savepoint_keeps_changes() {
+    let mut conn = connect().await;
+    let mut tx = begin(&mut conn).await;
+
+    insert(&mut tx, "outer").await.unwrap();
+    savepoint(&mut tx, |tx| Box::pin(async move { insert(tx, "inner").await })).await.unwrap();
+
+    assert_eq!(names(&mut tx).await, vec!["inner", "outer"]);
+}
+
+#[tokio::test]
+#[ignore]
+async fn failed_scope_is_rolled_back_and_outer_continues() {
+    let mut conn = connect().await;
+    let mut tx = begin(&mut conn).await;
+
+    insert(&mut tx, "before").await.unwrap();
+    let res: Result<(), Error> = savepoint(&mut tx, |tx| {
+        Box::pin(async move {
+            insert(tx, "inner").await?;
+            Err(Error::Validation { field: "name", message: "rejected".into() })
+        })
+    })
+    .await;
+    assert!(matches!(res, Err(Error::Validation { .. })));
+    insert(&mut tx, "after").await.unwrap();
+
+    assert_eq!(names(&mut tx).await, vec!["after", "before"]);
+}
+
+#[tokio::test]
+#[ignore]
+async fn sql_error_in_scope_does_not_abort_transaction() {
+    let mut conn = connect().await;
+    let mut tx = begin(&mut conn).await;
+
+    insert(&mut tx, "dup").await.unwrap();
+    let res = savepoint(&mut tx, |tx| {
+        Box::pin(async move {
+            insert(tx, "inner").await?;
+            insert(tx, "dup").await
+        })
+    })
+    .await;
+    assert!(matches!(res, Err(Error::UniqueViolation(_))), "{:?}", res);
+
+    // без ROLLBACK TO здесь была бы 25P02 in_failed_sql_transaction
+    insert(&mut tx, "after").await.unwrap();
+    assert_eq!(names(&mut tx).await, vec!["after", "dup"]);
+}
+
+#[tokio::test]
+#[ignore]
+async fn failed_middle_scope_discards_released_inner_scope() {
+    let mut conn = connect().await;
+    let mut tx = begin(&mut conn).await;
+
+    let res = savepoint(&mut tx, |tx| {
+        Box::pin(async move {
+            insert(tx, "middle").await?;
+            savepoint(tx, |tx| Box::pin(async move { insert(tx, "inner").await })).await?;
+            Err::<(), _>(Error::NotFound("middle".into()))
+        })
+    })
+    .await;
+    assert!(res.is_err());
+    assert!(names(&mut tx).await.is_empty());
+}
+
+#[tokio::test]
+#[ignore]
+async fn outer_scope_survives_failed_inner_scope() {
+    let mut conn = connect().await;
+    let mut tx = begin(&mut conn).await;
+
+    savepoint(&mut tx, |tx| {
+        Box::pin(async move {
+            insert(tx, "outer").await?;
+            let inner = savepoint(tx, |tx| {
+                Box::pin(async move {
+                    insert(tx, "inner").await?;
+                    insert(tx, "outer").await
+                })
+            })
+            .await;
+            assert!(inner.is_err());
+            insert(tx, "outer 2").await
+        })
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(names(&mut tx).await, vec!["outer", "outer 2"]);
+}
+
+#[tokio::test]
+#[ignore]
+async fn rollback_of_transaction_discards_released_scopes() {
+    let mut conn = connect().await;
+    {
+        let mut tx = conn.begin().await.unwrap();
+        sqlx::query("CREATE TABLE IF NOT EXISTS savepoint_rollback_test (name TEXT)").execute(&mut tx).await.unwrap();
+        savepoint(&mut tx, |tx| {
+            Box::pin(async move {
+                sqlx::query("INSERT INTO savepoint_rollback_test VALUES ('inner')").execute(tx).await?;
+                Ok(())
+            })
+        })
+        .await
+        .unwrap();
+        tx.rollback().await.unwrap();
+    }
+    let exists: Option<String> = sqlx::query_scalar("SELECT to_regclass('savepoint_rollback_test')::TEXT").fetch_one(&mut conn).await.unwrap();
+    assert_eq!(exists, None);
+}
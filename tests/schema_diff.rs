@@ -0,0 +1,126 @@
+// schema::diff на схемах, собранных в коде - без базы
+
+use sqlx_example::schema::{diff, Column, Difference, Schema, Table};
+
+fn column(name: &str, data_type: &str, udt_name: &str) -> Column {
+    Column { name: name.into(), data_type: data_type.into(), udt_name: udt_name.into(), nullable: true, default: None }
+}
+
+// todo из migrations/0001_init_todo.sql
+fn todo() -> Table {
+    let mut table = Table {
+        columns: vec![column("id", "integer", "int4"), column("name", "character varying(255)", "varchar")],
+        ..Table::default()
+    };
+    table.columns[0].nullable = false;
+    table.columns[0].default = Some("nextval('todo_id_seq'::regclass)".into());
+    table.indexes.insert("todo_pkey".into(), "CREATE UNIQUE INDEX todo_pkey ON public.todo USING btree (id)".into());
+    table.constraints.insert("todo_pkey".into(), "PRIMARY KEY (id)".into());
+    table
+}
+
+fn schema(tables: Vec<(&str, Table)>) -> Schema {
+    Schema { tables: tables.into_iter().map(|(name, table)| (name.to_string(), table)).collect() }
+}
+
+#[test]
+fn same_schema_has_no_differences() {
+    let expected = schema(vec![("todo", todo())]);
+    assert_eq!(diff(&expected, &expected.clone()), vec![]);
+}
+
+#[test]
+fn missing_and_extra_tables() {
+    let expected = schema(vec![("todo", todo()), ("outbox", Table::default())]);
+    let actual = schema(vec![("todo", todo()), ("legacy", Table::default())]);
+    let differences = diff(&expected, &actual);
+    assert_eq!(differences, vec![Difference::MissingTable("outbox".into()), Difference::ExtraTable("legacy".into())]);
+    assert_eq!(differences[0].to_string(), "- table outbox");
+    assert_eq!(differences[1].to_string(), "+ table legacy");
+}
+
+#[test]
+fn column_type_mismatch() {
+    let expected = schema(vec![("todo", todo())]);
+    let mut actual = todo();
+    actual.columns[1] = column("name", "text", "text");
+    let differences = diff(&expected, &schema(vec![("todo", actual)]));
+    assert_eq!(
+        differences,
+        vec![Difference::ChangedColumn {
+            table: "todo".into(),
+            column: "name".into(),
+            field: "type",
+            expected: "character varying(255)".into(),
+            actual: "text".into(),
+        }]
+    );
+    assert_eq!(differences[0].to_string(), "~ column todo.name type: expected `character varying(255)`, found `text`");
+}
+
+#[test]
+fn missing_extra_and_changed_columns() {
+    let expected = schema(vec![("todo", todo())]);
+    let mut actual = todo();
+    actual.columns[0].nullable = true;
+    actual.columns[1] = column("title", "character varying(255)", "varchar");
+    let differences = diff(&expected, &schema(vec![("todo", actual)]));
+    assert_eq!(
+        differences,
+        vec![
+            Difference::ChangedColumn {
+                table: "todo".into(),
+                column: "id".into(),
+                field: "nullable",
+                expected: "false".into(),
+                actual: "true".into(),
+            },
+            Difference::MissingColumn { table: "todo".into(), column: "name".into() },
+            Difference::ExtraColumn { table: "todo".into(), column: "title".into() },
+        ]
+    );
+}
+
+#[test]
+fn extra_and_changed_index() {
+    let expected = schema(vec![("todo", todo())]);
+    let mut actual = todo();
+    let definition = "CREATE INDEX todo_name ON public.todo USING btree (name)";
+    actual.indexes.insert("todo_name".into(), definition.into());
+    actual.constraints.insert("todo_pkey".into(), "PRIMARY KEY (id, name)".into());
+    let differences = diff(&expected, &schema(vec![("todo", actual)]));
+    assert_eq!(
+        differences,
+        vec![
+            Difference::ExtraIndex { table: "todo".into(), name: "todo_name".into(), definition: definition.into() },
+            Difference::ChangedConstraint {
+                table: "todo".into(),
+                name: "todo_pkey".into(),
+                expected: "PRIMARY KEY (id)".into(),
+                actual: "PRIMARY KEY (id, name)".into(),
+            },
+        ]
+    );
+    assert_eq!(differences[0].to_string(), format!("+ index todo_name on todo: {}", definition));
+
+    // индекс есть в миграциях, но не в базе
+    let differences = diff(&schema(vec![("todo", todo())]), &schema(vec![("todo", Table { indexes: Default::default(), ..todo() })]));
+    assert_eq!(
+        differences,
+        vec![Difference::MissingIndex {
+            table: "todo".into(),
+            name: "todo_pkey".into(),
+            definition: "CREATE UNIQUE INDEX todo_pkey ON public.todo USING btree (id)".into(),
+        }]
+    );
+}
+
+#[test]
+fn ignored_tables_are_not_compared() {
+    let mut expected = schema(vec![("todo", todo())]);
+    let mut actual = schema(vec![("todo", todo()), ("users", Table::default()), ("posts", Table::default())]);
+    let ignore = vec!["users".to_string(), "posts".to_string()];
+    expected.remove_tables(&ignore);
+    actual.remove_tables(&ignore);
+    assert_eq!(diff(&expected, &actual), vec![]);
+}
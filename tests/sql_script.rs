@@ -0,0 +1,108 @@
+// Разбор файла миграции на команды и правила lint - без базы
+
+use sqlx_example::migration_lint::{self, lint, Finding};
+use sqlx_example::sql_script::split;
+use std::path::Path;
+
+fn sqls(sql: &str) -> Vec<String> {
+    split(sql).into_iter().map(|statement| statement.sql).collect()
+}
+
+#[test]
+fn dollar_quoted_body_keeps_semicolons() {
+    let sql = "CREATE FUNCTION f() RETURNS int AS $body$ BEGIN RETURN 1; END; $body$ LANGUAGE plpgsql;\nSELECT $$a;b$$;";
+    assert_eq!(
+        sqls(sql),
+        vec!["CREATE FUNCTION f() RETURNS int AS $body$ BEGIN RETURN 1; END; $body$ LANGUAGE plpgsql", "SELECT $$a;b$$"]
+    );
+    // $1 - параметр, не начало кавычки
+    assert_eq!(sqls("SELECT $1; SELECT $2"), vec!["SELECT $1", "SELECT $2"]);
+}
+
+#[test]
+fn escaped_strings() {
+    assert_eq!(sqls(r"SELECT E'it\'s; fine'; SELECT 'it''s; fine';"), vec![r"SELECT E'it\'s; fine'", "SELECT 'it''s; fine'"]);
+    // в обычной строке \ не экранирует кавычку
+    assert_eq!(sqls(r"SELECT 'a\'; SELECT 2"), vec![r"SELECT 'a\'", "SELECT 2"]);
+}
+
+#[test]
+fn nested_block_comments() {
+    let statements = split("/* outer /* inner; */ still comment; */ SELECT 1; SELECT 2");
+    assert_eq!(statements.len(), 2);
+    assert_eq!(statements[0].comments, vec!["/* outer /* inner; */ still comment; */"]);
+    assert_eq!(statements[0].normalized(), "SELECT 1");
+}
+
+#[test]
+fn line_comment_inside_string_is_text() {
+    let statements = split("INSERT INTO t VALUES ('-- not a comment; really'); -- comment; here\nSELECT 1;");
+    assert_eq!(statements.len(), 2);
+    assert_eq!(statements[0].sql, "INSERT INTO t VALUES ('-- not a comment; really')");
+    assert!(statements[0].comments.is_empty());
+    // комментарий после `;` относится к следующей команде
+    assert_eq!(statements[1].comments, vec!["-- comment; here"]);
+    assert_eq!(statements[1].line, 2);
+}
+
+#[test]
+fn normalized_hides_literals_and_comments() {
+    let statements = split("ALTER   TABLE \"Todo\"\n  ADD name text DEFAULT 'DROP x' -- DROP y\n;");
+    assert_eq!(statements[0].normalized(), "ALTER TABLE \"Todo\" ADD name text DEFAULT ''");
+}
+
+fn rules(sql: &str, server_version: u32) -> Vec<&'static str> {
+    lint(Path::new("test.sql"), sql, server_version).into_iter().map(|finding: Finding| finding.rule).collect()
+}
+
+#[test]
+fn lint_allow_comment() {
+    assert_eq!(rules("CREATE INDEX i ON todo (name);", 15), vec![migration_lint::CREATE_INDEX_NOT_CONCURRENTLY]);
+    assert!(rules("-- lint:allow create-index-not-concurrently\nCREATE INDEX i ON todo (name);", 15).is_empty());
+    assert!(rules("CREATE INDEX i /* lint:allow all */ ON todo (name);", 15).is_empty());
+    // другое правило не отключает
+    assert_eq!(rules("-- lint:allow drop-column\nCREATE INDEX i ON todo (name);", 15), vec![migration_lint::CREATE_INDEX_NOT_CONCURRENTLY]);
+    // разрешение действует только на следующую команду
+    assert_eq!(
+        rules("-- lint:allow all\nALTER TABLE todo DROP COLUMN a;\nALTER TABLE todo DROP COLUMN b;", 15),
+        vec![migration_lint::DROP_COLUMN]
+    );
+}
+
+#[test]
+fn table_created_in_same_file_is_not_checked() {
+    let sql = "CREATE TABLE IF NOT EXISTS public.jobs (id int);\nCREATE INDEX jobs_id ON jobs (id);\nALTER TABLE jobs ALTER COLUMN id TYPE bigint;";
+    assert!(rules(sql, 15).is_empty());
+    // та же таблица из другого файла
+    assert_eq!(
+        rules("CREATE INDEX jobs_id ON jobs (id);\nALTER TABLE jobs ALTER COLUMN id TYPE bigint;", 15),
+        vec![migration_lint::CREATE_INDEX_NOT_CONCURRENTLY, migration_lint::ALTER_COLUMN_TYPE]
+    );
+}
+
+#[test]
+fn add_column_default_depends_on_server_version() {
+    let constant = "ALTER TABLE todo ADD COLUMN priority int NOT NULL DEFAULT 0;";
+    assert!(rules(constant, 11).is_empty());
+    assert_eq!(rules(constant, 10), vec![migration_lint::ADD_COLUMN_DEFAULT]);
+    // now() - stable, одно значение на всю команду
+    assert!(rules("ALTER TABLE todo ADD COLUMN seen timestamptz DEFAULT now();", 15).is_empty());
+
+    for volatile in [
+        "ALTER TABLE todo ADD COLUMN token uuid DEFAULT gen_random_uuid();",
+        "ALTER TABLE todo ADD COLUMN r float8 DEFAULT random();",
+        "ALTER TABLE todo ADD COLUMN n bigserial;",
+        "ALTER TABLE todo ADD COLUMN n int GENERATED ALWAYS AS IDENTITY;",
+    ] {
+        assert_eq!(rules(volatile, 15), vec![migration_lint::ADD_COLUMN_DEFAULT], "{}", volatile);
+    }
+    // DEFAULT внутри строки - не DEFAULT колонки
+    assert_eq!(rules("ALTER TABLE todo ADD COLUMN note text DEFAULT 'random()';", 10), vec![migration_lint::ADD_COLUMN_DEFAULT]);
+    assert!(rules("ALTER TABLE todo ADD COLUMN note text DEFAULT 'random()';", 15).is_empty());
+}
+
+#[test]
+fn concurrently_needs_no_transaction_header() {
+    assert_eq!(rules("CREATE INDEX CONCURRENTLY i ON todo (name);", 15), vec![migration_lint::CONCURRENTLY_IN_TRANSACTION]);
+    assert!(rules("-- no-transaction\nCREATE INDEX CONCURRENTLY i ON todo (name);", 15).is_empty());
+}
@@ -0,0 +1,119 @@
+// sqlx-data.json должен совпадать с запросами в коде и со схемой базы, иначе сборка без базы
+// (SQLX_OFFLINE=true) падает или проверяет типы по старой схеме.
+// Обновить: DATABASE_URL=... cargo sqlx prepare -- --lib
+//
+// Сверка с базой: cargo test --test sqlx_data -- --ignored
+
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use sqlx::{Connection, Executor, PgConnection};
+use sqlx_example::settings;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+const MACROS: &[&str] = &["query!(", "query_as!(", "query_scalar!("];
+
+fn data() -> Map<String, Value> {
+    let data: Value = serde_json::from_str(&std::fs::read_to_string("sqlx-data.json").unwrap()).unwrap();
+    let mut data = match data {
+        Value::Object(data) => data,
+        _ => panic!("sqlx-data.json is not an object"),
+    };
+    assert_eq!(data.remove("db"), Some(Value::from("PostgreSQL")));
+    data
+}
+
+// Все .rs под dir (рекурсивно), в которых есть query!/query_as!/query_scalar!
+fn sources(dir: &Path, out: &mut Vec<(PathBuf, String)>) {
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            sources(&path, out);
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            let source = std::fs::read_to_string(&path).unwrap();
+            if MACROS.iter().any(|name| source.contains(name)) {
+                out.push((path, source));
+            }
+        }
+    }
+}
+
+// как ключ в sqlx-data.json
+fn hash(query: &str) -> String {
+    hex::encode(Sha256::digest(query.as_bytes()))
+}
+
+// Первый строковый литерал после имени макроса: "..." или r#"..."#
+fn queries(source: &str) -> Vec<String> {
+    let mut queries = Vec::new();
+    for name in MACROS {
+        for (start, _) in source.match_indices(name) {
+            let args = &source[start + name.len()..];
+            let quote = args.find('"').unwrap();
+            let prefix = args[..quote].trim_end_matches('#');
+            let rest = &args[quote + 1..];
+            if prefix.ends_with('r') {
+                let end = rest.find(&format!("\"{}", "#".repeat(quote - prefix.len()))).unwrap();
+                queries.push(rest[..end].to_string());
+                continue;
+            }
+            let mut query = String::new();
+            let mut chars = rest.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' => match chars.next().unwrap() {
+                        'n' => query.push('\n'),
+                        't' => query.push('\t'),
+                        c => query.push(c),
+                    },
+                    c => query.push(c),
+                }
+            }
+            queries.push(query);
+        }
+    }
+    queries
+}
+
+#[test]
+fn every_query_is_prepared() {
+    let data = data();
+    let mut expected = BTreeSet::new();
+    let mut files = Vec::new();
+    sources(Path::new("src"), &mut files);
+    assert!(!files.is_empty(), "no query! macros found under src/");
+    for (path, source) in &files {
+        for query in queries(source) {
+            let hash = hash(&query);
+            assert!(data.contains_key(&hash), "{}: query is missing from sqlx-data.json, run cargo sqlx prepare:\n{}", path.display(), query);
+            expected.insert(hash);
+        }
+    }
+    let stale: Vec<&str> = data
+        .iter()
+        .filter(|(hash, _)| !expected.contains(*hash))
+        .map(|(_, entry)| entry["query"].as_str().unwrap())
+        .collect();
+    assert!(stale.is_empty(), "sqlx-data.json has queries that are no longer in the code, run cargo sqlx prepare: {:#?}", stale);
+}
+
+#[test]
+fn keys_are_query_hashes() {
+    for (key, entry) in data() {
+        assert_eq!(hash(entry["query"].as_str().unwrap()), key);
+    }
+}
+
+// Описание каждого запроса от текущей схемы должно совпадать с сохранённым
+#[tokio::test]
+#[ignore]
+async fn prepared_queries_match_database() {
+    let mut conn = PgConnection::connect(&settings::config().unwrap()).await.unwrap();
+    for (_, entry) in data() {
+        let query = entry["query"].as_str().unwrap();
+        let describe = serde_json::to_value(conn.describe(query).await.unwrap()).unwrap();
+        assert_eq!(describe, entry["describe"], "schema changed for query, run cargo sqlx prepare:\n{}", query);
+    }
+}
@@ -0,0 +1,94 @@
+// Импорт через staging таблицу, нужна база из settings/settings.toml: cargo test --test staging -- --ignored
+// Каждый тест импортирует в свою временную таблицу.
+
+use sqlx::{Connection, PgConnection};
+use sqlx_example::staging::{Column, ImportReport, StagingImport};
+use sqlx_example::{settings, Error};
+use std::path::PathBuf;
+
+async fn connect() -> PgConnection {
+    let mut conn = PgConnection::connect(&settings::config().unwrap()).await.unwrap();
+    sqlx::query(
+        "CREATE TEMPORARY TABLE staging_test (
+             id SERIAL PRIMARY KEY,
+             amount INT4 CHECK (amount >= 0),
+             day DATE,
+             checked BOOLEAN
+         )",
+    )
+    .execute(&mut conn)
+    .await
+    .unwrap();
+    conn
+}
+
+fn columns() -> Vec<Column> {
+    vec![Column::new("id", "INT4"), Column::new("amount", "INT4"), Column::new("day", "DATE"), Column::new("checked", "BOOLEAN")]
+}
+
+// CSV во временном файле и путь для report рядом
+fn files(name: &str, csv: &str) -> (PathBuf, PathBuf) {
+    let dir = std::env::temp_dir();
+    let path = dir.join(format!("staging_{}_{}.csv", name, std::process::id()));
+    let report = dir.join(format!("staging_{}_{}.rejected.csv", name, std::process::id()));
+    std::fs::write(&path, csv).unwrap();
+    let _ = std::fs::remove_file(&report);
+    (path, report)
+}
+
+#[tokio::test]
+#[ignore]
+async fn bad_values_go_to_report() {
+    let mut conn = connect().await;
+    let (csv, report) = files("bad_values", "id,amount,day,checked\n1,10,2024-01-02,true\n2,abc,,\n3,99999999999,,\n4,,2024-13-45,\n5,,,maybe\n10,,,\n");
+
+    let result = StagingImport::new("staging_test", columns()).key(&["id"]).run(&mut conn, &csv, Some(&report)).await.unwrap();
+    assert_eq!(result, ImportReport { total: 6, imported: 2, rejected: 4 });
+    let rejected = std::fs::read_to_string(&report).unwrap();
+    // с заголовком
+    assert_eq!(rejected.lines().count(), 5, "{}", rejected);
+    assert!(rejected.contains("amount: not a valid INT4"));
+    assert!(rejected.contains("day: not a valid DATE"));
+    assert!(rejected.contains("checked: not a valid BOOLEAN"));
+
+    // последовательность поднята вместе с импортом
+    let id: i32 = sqlx::query_scalar("INSERT INTO staging_test DEFAULT VALUES RETURNING id").fetch_one(&mut conn).await.unwrap();
+    assert_eq!(id, 11);
+
+    std::fs::remove_file(csv).unwrap();
+    std::fs::remove_file(report).unwrap();
+}
+
+// CHECK проверяет только INSERT: импорт откатывается, report не остаётся
+#[tokio::test]
+#[ignore]
+async fn failed_merge_leaves_no_report() {
+    let mut conn = connect().await;
+    let (csv, report) = files("failed_merge", "id,amount,day,checked\n1,-1,,\n2,abc,,\n");
+
+    let err = StagingImport::new("staging_test", columns()).run(&mut conn, &csv, Some(&report)).await.unwrap_err();
+    assert!(matches!(err, Error::CheckViolation(_)), "{:?}", err);
+    assert!(!report.exists());
+    let count: i64 = sqlx::query_scalar("SELECT count(*) FROM staging_test").fetch_one(&mut conn).await.unwrap();
+    assert_eq!(count, 0);
+
+    std::fs::remove_file(csv).unwrap();
+}
+
+#[tokio::test]
+#[ignore]
+async fn sql_type_is_checked() {
+    let mut conn = connect().await;
+    let (csv, report) = files("sql_type", "id\n1\n");
+
+    let columns = vec![Column::new("id", "INT4) RETURNS INT4 AS $$ SELECT 1 $$; --")];
+    let err = StagingImport::new("staging_test", columns).run(&mut conn, &csv, Some(&report)).await.unwrap_err();
+    assert!(matches!(err, Error::Validation { field: "sql_type", .. }), "{:?}", err);
+    assert!(!report.exists());
+
+    // модификаторы и массивы допустимы
+    let columns = vec![Column::new("id", "NUMERIC(10, 0)")];
+    StagingImport::new("staging_test", columns).run(&mut conn, &csv, None).await.unwrap();
+
+    std::fs::remove_file(csv).unwrap();
+}
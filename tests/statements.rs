@@ -0,0 +1,95 @@
+// Нужна база из settings/settings.toml: cargo test --test statements -- --ignored
+
+use sqlx::{Connection, PgConnection};
+use sqlx_example::statements::{Preparation, StatementStats, Statements};
+use sqlx_example::{settings, Error};
+
+async fn connect() -> PgConnection {
+    PgConnection::connect(&settings::config().unwrap()).await.unwrap()
+}
+
+fn statements(preparation: Preparation) -> Statements {
+    Statements::new(preparation)
+        .declare("add", "SELECT $1 + $2", &["INT4", "INT4"])
+        .declare("upper", "SELECT upper($1)", &["TEXT"])
+}
+
+async fn add(statements: &Statements, conn: &mut PgConnection, a: i32, b: i32) -> i32 {
+    let query = statements.query("add", conn).await.unwrap();
+    sqlx::Row::get(&query.bind(a).bind(b).fetch_one(conn).await.unwrap(), 0)
+}
+
+fn stats(name: &'static str, hits: u64, misses: u64) -> StatementStats {
+    StatementStats { name, hits, misses, unknown: 0 }
+}
+
+#[test]
+fn unknown_statement() {
+    assert!(matches!(statements(Preparation::Persistent).sql("missing"), Err(Error::NotFound(_))));
+}
+
+#[tokio::test]
+#[ignore]
+async fn persistent_statement_is_prepared_once_per_connection() {
+    let statements = statements(Preparation::Persistent);
+    let mut first = connect().await;
+    let mut second = connect().await;
+
+    assert_eq!(add(&statements, &mut first, 1, 2).await, 3);
+    // вместе с запросом кэшируется поиск oid типов по имени
+    let cached = sqlx::Connection::cached_statements_size(&first);
+    assert_eq!(add(&statements, &mut first, 3, 4).await, 7);
+    assert_eq!(add(&statements, &mut second, 5, 6).await, 11);
+    assert_eq!(add(&statements, &mut second, 7, 8).await, 15);
+
+    assert_eq!(statements.stats(), vec![stats("add", 2, 2), stats("upper", 0, 0)]);
+    assert_eq!(sqlx::Connection::cached_statements_size(&first), cached);
+}
+
+#[tokio::test]
+#[ignore]
+async fn unnamed_statement_is_not_cached() {
+    let statements = statements(Preparation::Unnamed);
+    let mut conn = connect().await;
+    let cached = sqlx::Connection::cached_statements_size(&conn);
+
+    assert_eq!(add(&statements, &mut conn, 1, 2).await, 3);
+    assert_eq!(add(&statements, &mut conn, 3, 4).await, 7);
+    let upper: (String,) = statements
+        .query_as_with("upper", &mut conn, {
+            let mut arguments = sqlx::postgres::PgArguments::default();
+            sqlx::Arguments::add(&mut arguments, "pgbouncer");
+            arguments
+        })
+        .await
+        .unwrap()
+        .fetch_one(&mut conn)
+        .await
+        .unwrap();
+    assert_eq!(upper.0, "PGBOUNCER");
+
+    assert_eq!(statements.stats(), vec![stats("add", 0, 2), stats("upper", 0, 1)]);
+    assert_eq!(sqlx::Connection::cached_statements_size(&conn), cached);
+}
+
+// Кэш заполнен другими запросами: add вытеснен, но по размеру кэша этого не видно - unknown.
+// Сам кэш при этом не очищается.
+#[tokio::test]
+#[ignore]
+async fn full_cache_is_not_counted() {
+    let statements = statements(Preparation::Persistent);
+    let mut conn = PgConnection::connect_with(&settings::connect_options().unwrap()).await.unwrap();
+
+    assert_eq!(add(&statements, &mut conn, 1, 2).await, 3);
+    assert_eq!(add(&statements, &mut conn, 3, 4).await, 7);
+    assert_eq!(statements.stats()[0], stats("add", 1, 1));
+
+    for i in 0..settings::STATEMENT_CACHE_CAPACITY {
+        sqlx::query(&format!("SELECT $1::INT4 + {}", i)).bind(1).execute(&mut conn).await.unwrap();
+    }
+    assert_eq!(sqlx::Connection::cached_statements_size(&conn), settings::STATEMENT_CACHE_CAPACITY);
+    assert_eq!(add(&statements, &mut conn, 5, 6).await, 11);
+    assert_eq!(add(&statements, &mut conn, 7, 8).await, 15);
+    assert_eq!(statements.stats()[0], StatementStats { unknown: 2, ..stats("add", 1, 1) });
+    assert_eq!(sqlx::Connection::cached_statements_size(&conn), settings::STATEMENT_CACHE_CAPACITY);
+}
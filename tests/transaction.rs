@@ -0,0 +1,83 @@
+// Повтор транзакции при 40001, нужна база с миграциями: cargo test --test transaction -- --ignored
+// Конфликт создаётся честно: пока замыкание держит снимок, другая SERIALIZABLE транзакция
+// меняет ту же строку и коммитит, UPDATE в замыкании получает serialization_failure.
+
+use sqlx::postgres::PgPoolOptions;
+use sqlx::{Connection, PgPool};
+use sqlx_example::transaction::{with_transaction_retry, IsolationLevel, Retry};
+use sqlx_example::{settings, Error};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+async fn pool() -> PgPool {
+    PgPoolOptions::new().max_connections(3).connect_with(settings::connect_options().unwrap()).await.unwrap()
+}
+
+async fn insert_todo(pool: &PgPool) -> i32 {
+    sqlx::query_scalar("INSERT INTO todo (name) VALUES ('retry test') RETURNING id").fetch_one(pool).await.unwrap()
+}
+
+// Первые conflicts попыток проигрывают конкурирующей транзакции
+async fn rename_with_conflicts(pool: &PgPool, id: i32, conflicts: u32, max_attempts: u32) -> (Result<(), Error>, u32) {
+    let attempts = Arc::new(AtomicU32::new(0));
+    let retry = Retry { max_attempts, base_delay: Duration::from_millis(1), max_delay: Duration::from_millis(10) };
+    let result = with_transaction_retry(pool, IsolationLevel::Serializable, retry, |tx| {
+        let attempts = attempts.clone();
+        let pool = pool.clone();
+        Box::pin(async move {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst) + 1;
+            let _: String = sqlx::query_scalar("SELECT name FROM todo WHERE id = $1").bind(id).fetch_one(&mut *tx).await?;
+            if attempt <= conflicts {
+                let mut other = pool.acquire().await?;
+                let mut other = other.begin().await?;
+                sqlx::query("SET TRANSACTION ISOLATION LEVEL SERIALIZABLE").execute(&mut other).await?;
+                sqlx::query("UPDATE todo SET name = $2 WHERE id = $1").bind(id).bind(format!("other {}", attempt)).execute(&mut other).await?;
+                other.commit().await?;
+            }
+            sqlx::query("UPDATE todo SET name = 'retried' WHERE id = $1").bind(id).execute(&mut *tx).await?;
+            Ok(())
+        })
+    })
+    .await;
+    (result, attempts.load(Ordering::SeqCst))
+}
+
+async fn name(pool: &PgPool, id: i32) -> String {
+    sqlx::query_scalar("SELECT name FROM todo WHERE id = $1").bind(id).fetch_one(pool).await.unwrap()
+}
+
+async fn delete_todo(pool: &PgPool, id: i32) {
+    sqlx::query("DELETE FROM todo WHERE id = $1").bind(id).execute(pool).await.unwrap();
+}
+
+#[tokio::test]
+#[ignore]
+async fn serialization_failure_is_retried() {
+    let pool = pool().await;
+    let id = insert_todo(&pool).await;
+
+    let (result, attempts) = rename_with_conflicts(&pool, id, 2, 5).await;
+    result.unwrap();
+    assert_eq!(attempts, 3);
+    assert_eq!(name(&pool, id).await, "retried");
+
+    delete_todo(&pool, id).await;
+}
+
+#[tokio::test]
+#[ignore]
+async fn retries_are_exhausted() {
+    let pool = pool().await;
+    let id = insert_todo(&pool).await;
+
+    let (result, attempts) = rename_with_conflicts(&pool, id, u32::MAX, 3).await;
+    let err = result.unwrap_err();
+    assert!(matches!(err, Error::SerializationFailure(_)), "{:?}", err);
+    assert_eq!(err.database_error().map(|err| err.code.as_str()), Some("40001"));
+    assert_eq!(attempts, 3);
+    // последняя конкурирующая транзакция победила
+    assert_eq!(name(&pool, id).await, "other 3");
+
+    delete_todo(&pool, id).await;
+}